@@ -1,4 +1,3 @@
-#![feature(used)]
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
@@ -6,18 +5,34 @@ extern crate zcfg;
 extern crate zcfg_flag_parser;
 
 use std::env;
+use std::process;
+use zcfg::InitErr;
+use zcfg_flag_parser::FlagInitErr;
 use zcfg_flag_parser::FlagParser;
+use zcfg_flag_parser::ParseOutcome;
 
 define_cfg!(greeting, String, "Hello".to_owned(),
             "Defines what the greeter should say (such as \"Hello\")");
 define_cfg!(multigreeting, Option<Vec<String>>, None,
-            "A comma-separated set of greetings to use. Overrides `--greeting`, if set.");
+            "A comma-separated set of greetings to use, quoted (as in `\"Hi, you\",Hey`) if a greeting \
+             contains a comma. Overrides `--greeting`, if set.");
 define_cfg!(greeting_target, String, "World".to_owned(),
             "Defines what the greeter should say hello to (such as \"World\")");
 
 fn main() {
-  let errs = FlagParser::new().parse_from_args(env::args().skip(1));
-  assert_eq!(errs, Ok(()));
+  match FlagParser::new().parse_from_args(env::args().skip(1)) {
+    Ok(ParseOutcome::Parsed(_)) => {},
+    Ok(ParseOutcome::HelpRequested) => return,
+    Err(errs) => {
+      for err in errs.iter() {
+        eprintln!("error: {}", err);
+        if let FlagInitErr::InitErr { err: InitErr::FailedToParse { ref err, .. }, .. } = *err {
+          eprintln!("{}", err.underline());
+        }
+      }
+      process::exit(1)
+    },
+  }
 
   let mut greeting = greeting::CONFIG.get_value();
   if let Some(greetings) =  multigreeting::CONFIG.get_value() {
@@ -0,0 +1,62 @@
+/*!
+ * Compares config reads through the old storage, an `Arc<RwLock<T>>` that is locked and cloned on
+ * every read, against the atomic and `ArcSwap` storage that `define_cfg!` now uses.
+ */
+
+#[macro_use]
+extern crate criterion;
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate zcfg;
+
+use criterion::{black_box, Criterion};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+define_cfg!(bench_u64, u64, 42u64,
+            "a primitive configuration, stored in an atomic");
+define_cfg!(bench_string, String, "a moderately sized string value".to_owned(),
+            "a string configuration, stored in a swapped Arc");
+
+lazy_static! {
+  static ref OLD_U64: Arc<RwLock<u64>> = Arc::new(RwLock::new(42));
+  static ref OLD_STRING: Arc<RwLock<String>> =
+    Arc::new(RwLock::new("a moderately sized string value".to_owned()));
+}
+
+fn old_read<T: Clone>(value: &Arc<RwLock<T>>) -> T {
+  value.read().expect("somebody soiled a config").clone()
+}
+
+fn single_threaded(c: &mut Criterion) {
+  let mut group = c.benchmark_group("read");
+  group.bench_function("u64/rwlock", |b| b.iter(|| black_box(old_read(&OLD_U64))));
+  group.bench_function("u64/atomic", |b| b.iter(|| black_box(bench_u64::CONFIG.get_value())));
+  group.bench_function("string/rwlock", |b| b.iter(|| black_box(old_read(&OLD_STRING))));
+  group.bench_function("string/arc_swap", |b| b.iter(|| black_box(bench_string::CONFIG.get_value())));
+  group.finish();
+}
+
+/** Runs `read` on four threads at once, which is where the shared lock's cache traffic shows. */
+fn contended<F: Fn() + Sync>(read: F) {
+  let read = &read;
+  thread::scope(|scope| {
+    for _ in 0..4 {
+      scope.spawn(move || for _ in 0..1000 { read() });
+    }
+  });
+}
+
+fn multi_threaded(c: &mut Criterion) {
+  let mut group = c.benchmark_group("contended_read");
+  group.bench_function("u64/rwlock", |b| b.iter(|| contended(|| { black_box(old_read(&OLD_U64)); })));
+  group.bench_function("u64/atomic", |b| b.iter(|| contended(|| { black_box(bench_u64::CONFIG.get_value()); })));
+  group.bench_function("string/rwlock", |b| b.iter(|| contended(|| { black_box(old_read(&OLD_STRING)); })));
+  group.bench_function("string/arc_swap",
+                       |b| b.iter(|| contended(|| { black_box(bench_string::CONFIG.get_value()); })));
+  group.finish();
+}
+
+criterion_group!(benches, single_threaded, multi_threaded);
+criterion_main!(benches);
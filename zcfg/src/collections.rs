@@ -0,0 +1,200 @@
+/*!
+ * Map and set configs, which use the syntax described in the `list` module.
+ *
+ * Maps are written as entries, like `a=1,b=2`, and sets as elements, like `a,b`. A key or element
+ * that appears twice is an error, since one of the two would otherwise be silently dropped.
+ */
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use list;
+use ConfigParseable;
+use ParseErr;
+
+impl <K, V> ConfigParseable for HashMap<K, V>
+    where K: ConfigParseable, K::Output: Eq + Hash, V: ConfigParseable {
+  type Output = HashMap<K::Output, V::Output>;
+  fn parse_from_str(s: &str) -> Result<Self::Output, ParseErr> {
+    let mut map = HashMap::new();
+    parse_entries::<K, V, _>(s, |k, v| map.insert(k, v).is_none())?;
+    Ok(map)
+  }
+
+  /** Entries are sorted, so that the rendered value does not depend on the hash order. */
+  fn display_value(value: &Self::Output) -> Option<String> {
+    display_entries::<K, V, _>(value.iter(), true)
+  }
+
+  fn accumulates() -> bool {
+    true
+  }
+}
+
+impl <K, V> ConfigParseable for BTreeMap<K, V>
+    where K: ConfigParseable, K::Output: Ord, V: ConfigParseable {
+  type Output = BTreeMap<K::Output, V::Output>;
+  fn parse_from_str(s: &str) -> Result<Self::Output, ParseErr> {
+    let mut map = BTreeMap::new();
+    parse_entries::<K, V, _>(s, |k, v| map.insert(k, v).is_none())?;
+    Ok(map)
+  }
+
+  fn display_value(value: &Self::Output) -> Option<String> {
+    display_entries::<K, V, _>(value.iter(), false)
+  }
+
+  fn accumulates() -> bool {
+    true
+  }
+}
+
+impl <T> ConfigParseable for HashSet<T> where T: ConfigParseable, T::Output: Eq + Hash {
+  type Output = HashSet<T::Output>;
+  fn parse_from_str(s: &str) -> Result<Self::Output, ParseErr> {
+    let mut set = HashSet::new();
+    parse_elements::<T, _>(s, |v| set.insert(v))?;
+    Ok(set)
+  }
+
+  /** Elements are sorted, so that the rendered value does not depend on the hash order. */
+  fn display_value(value: &Self::Output) -> Option<String> {
+    display_elements::<T, _>(value.iter(), true)
+  }
+
+  fn accumulates() -> bool {
+    true
+  }
+}
+
+impl <T> ConfigParseable for BTreeSet<T> where T: ConfigParseable, T::Output: Ord {
+  type Output = BTreeSet<T::Output>;
+  fn parse_from_str(s: &str) -> Result<Self::Output, ParseErr> {
+    let mut set = BTreeSet::new();
+    parse_elements::<T, _>(s, |v| set.insert(v))?;
+    Ok(set)
+  }
+
+  fn display_value(value: &Self::Output) -> Option<String> {
+    display_elements::<T, _>(value.iter(), false)
+  }
+
+  fn accumulates() -> bool {
+    true
+  }
+}
+
+/** Parses each entry of a map, passing it to `insert`, which returns false for a duplicate key. */
+fn parse_entries<K, V, F>(s: &str, mut insert: F) -> Result<(), ParseErr>
+    where K: ConfigParseable, V: ConfigParseable, F: FnMut(K::Output, V::Output) -> bool {
+  for (idx, (entry, span)) in list::split_spanned(s)?.into_iter().enumerate() {
+    let entry_err = |cause| list::element_err(s, idx, span.clone(), cause);
+    let (key, value) = list::split_entry(&entry).map_err(entry_err)?;
+    let value_start = entry.len() - value.len();
+    let key_err = |message: String| ParseErr::new(&entry, message).at(0..value_start - 1);
+
+    let parsed_key = K::parse_from_str(&key)
+      .map_err(|e| entry_err(key_err(format!("key `{}`", key)).caused_by(e)))?;
+    let parsed_value = V::parse_from_str(value)
+      .map_err(|e| {
+        let value_err = ParseErr::new(&entry, format!("value for `{}`", key)).at(value_start..entry.len());
+        entry_err(value_err.caused_by(e))
+      })?;
+    if !insert(parsed_key, parsed_value) {
+      return Err(entry_err(key_err(format!("duplicate key `{}`", key))))
+    }
+  }
+  Ok(())
+}
+
+/** Parses each element of a set, passing it to `insert`, which returns false for a duplicate. */
+fn parse_elements<T, F>(s: &str, mut insert: F) -> Result<(), ParseErr>
+    where T: ConfigParseable, F: FnMut(T::Output) -> bool {
+  for (idx, (element, span)) in list::split_spanned(s)?.into_iter().enumerate() {
+    let parsed = T::parse_from_str(&element).map_err(|e| list::element_err(s, idx, span.clone(), e))?;
+    if !insert(parsed) {
+      let duplicate = ParseErr::new(&element, format!("duplicate element `{}`", element));
+      return Err(list::element_err(s, idx, span, duplicate))
+    }
+  }
+  Ok(())
+}
+
+fn display_entries<'a, K, V, I>(entries: I, sort: bool) -> Option<String>
+    where K: ConfigParseable, V: ConfigParseable, K::Output: 'a, V::Output: 'a,
+          I: Iterator<Item = (&'a K::Output, &'a V::Output)> {
+  let mut rendered = entries
+    .map(|(k, v)| Some(list::join_entry(&K::display_value(k)?, &V::display_value(v)?)))
+    .collect::<Option<Vec<String>>>()?;
+  if sort {
+    rendered.sort();
+  }
+  Some(list::join(&rendered))
+}
+
+fn display_elements<'a, T, I>(elements: I, sort: bool) -> Option<String>
+    where T: ConfigParseable, T::Output: 'a, I: Iterator<Item = &'a T::Output> {
+  let mut rendered = elements
+    .map(|v| T::display_value(v))
+    .collect::<Option<Vec<String>>>()?;
+  if sort {
+    rendered.sort();
+  }
+  Some(list::join(&rendered))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn maps_parse_entries() {
+    let weights = BTreeMap::<String, u32>::parse_from_str("a=1,b=2").unwrap();
+    assert_eq!(weights.into_iter().collect::<Vec<_>>(), vec![("a".to_owned(), 1), ("b".to_owned(), 2)]);
+
+    let escaped = HashMap::<String, String>::parse_from_str("\"\\\"k=1\\\"=x, y\",k\\,2=a\\,b").unwrap();
+    assert_eq!(escaped.get("k=1"), Some(&"x, y".to_owned()));
+    assert_eq!(escaped.get("k,2"), Some(&"a,b".to_owned()));
+    assert_eq!(BTreeMap::<String, u32>::parse_from_str(""), Ok(BTreeMap::new()));
+  }
+
+  fn err_string<T: ConfigParseable>(s: &str) -> String {
+    T::parse_from_str(s).err().expect("the value should not parse").to_string()
+  }
+
+  #[test]
+  fn malformed_maps_report_the_entry() {
+    assert_eq!(err_string::<HashMap<String, u32>>("a=1,a=2"), "list element 1: duplicate key `a`".to_owned());
+    assert_eq!(err_string::<HashMap<u32, u32>>("1=1,x=2"),
+               "list element 1: key `x`: invalid digit found in string, expected u32".to_owned());
+    assert_eq!(err_string::<HashMap<String, u32>>("a=x"),
+               "list element 0: value for `a`: invalid digit found in string, expected u32".to_owned());
+    assert_eq!(err_string::<HashMap<String, u32>>("a=1,b"),
+               "list element 1: missing `=`, expected an entry like `key=value`".to_owned());
+  }
+
+  #[test]
+  fn map_errors_locate_the_bad_part() {
+    let err = HashMap::<String, u32>::parse_from_str("a=1,bb=x").unwrap_err();
+    assert_eq!(err.span(), 4..8);
+    let entry_err = err.cause().unwrap();
+    assert_eq!((entry_err.input(), entry_err.span()), ("bb=x", 3..4));
+    assert_eq!(entry_err.cause().unwrap().input(), "x");
+  }
+
+  #[test]
+  fn sets_reject_duplicates() {
+    assert_eq!(BTreeSet::<u32>::parse_from_str("3,1,2").unwrap().into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(err_string::<HashSet<String>>("a,b,a"), "list element 2: duplicate element `a`".to_owned());
+  }
+
+  #[test]
+  fn maps_and_sets_round_trip() {
+    let map = HashMap::<String, String>::parse_from_str("z=1,\"\\\"a=b\\\"=c, d\",m=[x]").unwrap();
+    let displayed = HashMap::<String, String>::display_value(&map).unwrap();
+    assert_eq!(displayed, "\"\\\"a=b\\\"=c, d\",m=[x],z=1".to_owned());
+    assert_eq!(HashMap::<String, String>::parse_from_str(&displayed), Ok(map));
+
+    let set = HashSet::<String>::parse_from_str("b,\"a,c\"").unwrap();
+    assert_eq!(HashSet::<String>::display_value(&set), Some("\"a,c\",b".to_owned()));
+  }
+}
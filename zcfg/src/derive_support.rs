@@ -0,0 +1,170 @@
+/*!
+ * Helpers for the code generated by `#[derive(ConfigParseable)]` in `zcfg_derive`.
+ *
+ * The derived syntax is:
+ *
+ * - `Variant` for unit enum variants, matched case-insensitively.
+ * - `Variant(value;value)` for tuple variants.
+ * - `Variant(key=value;key=value)` for struct variants.
+ * - `key=value;key=value` for structs.
+ *
+ * Separators inside parentheses belong to the nested value, so `outer=Inner(a=1;b=2);other=3`
+ * has two fields.
+ */
+
+use ConfigParseable;
+use ParseErr;
+
+/** Splits `s` on `;` outside of parentheses. An empty string has no fields. */
+pub fn split_fields(s: &str) -> Vec<&str> {
+  if s.is_empty() {
+    return Vec::new()
+  }
+
+  let mut fields = Vec::new();
+  let mut depth = 0usize;
+  let mut start = 0;
+  for (idx, c) in s.char_indices() {
+    match c {
+      '(' => depth += 1,
+      ')' => depth = depth.saturating_sub(1),
+      ';' if depth == 0 => {
+        fields.push(&s[start..idx]);
+        start = idx + 1;
+      },
+      _ => {},
+    }
+  }
+  fields.push(&s[start..]);
+  fields
+}
+
+/** Splits `Name(args)` into its trimmed name and args. A bare `Name` has no args. */
+pub fn split_variant(s: &str) -> Result<(&str, Option<&str>), ParseErr> {
+  match s.find('(') {
+    None => Ok((s.trim(), None)),
+    Some(open_idx) => {
+      if !s.ends_with(')') {
+        return Err(ParseErr::new(s, format!("expected `)` at the end of `{}`", s)).at(s.len()..s.len()))
+      }
+      Ok((s[..open_idx].trim(), Some(&s[open_idx + 1..s.len() - 1])))
+    },
+  }
+}
+
+/** Splits `value;value`, a part of `input`, into exactly `count` values. */
+pub fn tuple_values<'a>(input: &str, s: &'a str, count: usize) -> Result<Vec<&'a str>, ParseErr> {
+  let values = if count == 1 { vec![s] } else { split_fields(s) };
+  if values.len() != count {
+    return Err(err_in(input, s, format!("expected {} values separated by `;`, found {}", count, values.len())))
+  }
+  Ok(values)
+}
+
+/**
+ * Assigns each `key=value` in `s`, a part of `input`, to the field with that key.
+ *
+ * The result holds a value for each of `keys`, in order, or `None` for missing keys.
+ */
+pub fn key_values<'a>(input: &str, s: &'a str, keys: &[&str]) -> Result<Vec<Option<&'a str>>, ParseErr> {
+  let mut values = vec![None; keys.len()];
+  for field in split_fields(s) {
+    let eq_idx = field.find('=')
+      .ok_or_else(|| err_in(input, field, format!("expected `key=value`, found `{}`", field)))?;
+    let key = field[..eq_idx].trim();
+    let key_idx = keys.iter().position(|k| *k == key).ok_or_else(|| {
+      err_in(input, key, format!("unknown field `{}`", key)).expecting(format!("one of: {}", keys.join(", ")))
+    })?;
+    if values[key_idx].is_some() {
+      return Err(err_in(input, key, format!("duplicate field `{}`", key)))
+    }
+    values[key_idx] = Some(&field[eq_idx + 1..]);
+  }
+  Ok(values)
+}
+
+/** Parses a field's value, a part of `input`, naming the field in any error. */
+pub fn parse_field<T: ConfigParseable>(input: &str, key: &str, value: &str) -> Result<T::Output, ParseErr> {
+  T::parse_from_str(value).map_err(|e| err_in(input, value, format!("field `{}`", key)).caused_by(e))
+}
+
+/** Parses a field's value, which must be present. */
+pub fn parse_required_field<T: ConfigParseable>(input: &str, key: &str, value: Option<&str>)
+    -> Result<T::Output, ParseErr> {
+  match value {
+    None => Err(ParseErr::new(input, format!("missing field `{}`", key))),
+    Some(value) => parse_field::<T>(input, key, value),
+  }
+}
+
+/** An error about `part`, which must be a slice of `input`. */
+pub fn err_in<M: Into<String>>(input: &str, part: &str, message: M) -> ParseErr {
+  let start = (part.as_ptr() as usize).wrapping_sub(input.as_ptr() as usize);
+  let err = ParseErr::new(input, message);
+  if start <= input.len() && start + part.len() <= input.len() {
+    err.at(start..start + part.len())
+  } else {
+    err
+  }
+}
+
+/** Joins rendered values with `;`, or gives `None` if any value could not be rendered. */
+pub fn join_fields(values: Vec<Option<String>>) -> Option<String> {
+  values.into_iter().collect::<Option<Vec<String>>>().map(|values| values.join(";"))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn fields_split_outside_parens() {
+    assert_eq!(split_fields(""), Vec::<&str>::new());
+    assert_eq!(split_fields("a=1;b=X(c=2;d=3);e="), vec!["a=1", "b=X(c=2;d=3)", "e="]);
+  }
+
+  fn err_string<T>(result: Result<T, ParseErr>) -> String {
+    result.err().expect("expected an error").to_string()
+  }
+
+  #[test]
+  fn variants_split_into_name_and_args() {
+    assert_eq!(split_variant(" Local "), Ok(("Local", None)));
+    assert_eq!(split_variant("Remote(a(b);c)"), Ok(("Remote", Some("a(b);c"))));
+    assert_eq!(err_string(split_variant("Remote(a")), "expected `)` at the end of `Remote(a`");
+  }
+
+  #[test]
+  fn tuple_values_are_counted() {
+    assert_eq!(tuple_values("a;b", "a;b", 1), Ok(vec!["a;b"]));
+    assert_eq!(tuple_values("a;b", "a;b", 2), Ok(vec!["a", "b"]));
+    assert_eq!(err_string(tuple_values("a", "a", 2)), "expected 2 values separated by `;`, found 1");
+  }
+
+  #[test]
+  fn key_values_are_assigned() {
+    let keys = &["a", "b", "c"];
+    assert_eq!(key_values("", "b=2; a = x=1", keys), Ok(vec![Some(" x=1"), Some("2"), None]));
+    assert_eq!(err_string(key_values("", "a=1;a=2", keys)), "duplicate field `a`");
+    assert_eq!(err_string(key_values("", "z=1", &["a", "b"])), "unknown field `z`, expected one of: a, b");
+    assert_eq!(err_string(key_values("", "a", keys)), "expected `key=value`, found `a`");
+  }
+
+  #[test]
+  fn errors_locate_the_field() {
+    let input = "X(a=y;zz=2)";
+    let args = &input[2..10];
+    assert_eq!(key_values(input, args, &["a"]).unwrap_err().span(), 6..8);
+
+    let err = parse_field::<u32>(input, "a", &input[4..5]).unwrap_err();
+    assert_eq!((err.input(), err.span()), (input, 4..5));
+  }
+
+  #[test]
+  fn fields_report_their_key() {
+    assert_eq!(parse_required_field::<u32>("", "port", Some("80")), Ok(80));
+    assert_eq!(err_string(parse_required_field::<u32>("", "port", None)), "missing field `port`");
+    assert_eq!(err_string(parse_required_field::<u32>("", "port", Some("x"))),
+               "field `port`: invalid digit found in string, expected u32");
+  }
+}
@@ -0,0 +1,140 @@
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::time::Duration;
+use ConfigParseable;
+use ParseErr;
+
+/** Duration units, from largest to smallest, with their length in nanoseconds. */
+const UNITS: &[(&str, u128)] = &[
+  ("d", 86_400_000_000_000),
+  ("h", 3_600_000_000_000),
+  ("m", 60_000_000_000),
+  ("s", 1_000_000_000),
+  ("ms", 1_000_000),
+  ("us", 1_000),
+  ("ns", 1),
+];
+
+/**
+ * Durations are written as a number and a unit, like `1500ms`, `30s` or `2h`.
+ *
+ * The units are `d`, `h`, `m`, `s`, `ms`, `us` (or `µs`) and `ns`. Numbers may have a fraction,
+ * as in `1.5s`, and terms may be combined, as in `1h30m`. A bare `0` is also accepted.
+ */
+impl ConfigParseable for Duration {
+  type Output = Duration;
+  fn parse_from_str(s: &str) -> Result<Duration, ParseErr> {
+    parse_duration(s).map_err(|span| {
+      ParseErr::new(s, format!("invalid duration `{}`", s))
+        .at(span)
+        .expecting("a number and a unit (d, h, m, s, ms, us, ns), like `1500ms` or `1h30m`")
+    })
+  }
+
+  /** Renders the duration in the largest unit that represents it exactly. */
+  fn display_value(value: &Duration) -> Option<String> {
+    let nanos = value.as_nanos();
+    if nanos == 0 {
+      return Some("0s".to_owned())
+    }
+    UNITS.iter()
+      .find(|&&(_, unit_nanos)| nanos.is_multiple_of(unit_nanos))
+      .map(|&(unit, unit_nanos)| format!("{}{}", nanos / unit_nanos, unit))
+  }
+}
+
+/** Parses a duration, or returns the byte range of the term that could not be parsed. */
+fn parse_duration(s: &str) -> Result<Duration, Range<usize>> {
+  let leading_space = s.len() - s.trim_start().len();
+  let trimmed = s.trim();
+  match trimmed {
+    "" => return Err(0..s.len()),
+    "0" => return Ok(Duration::from_secs(0)),
+    _ => {},
+  }
+
+  let mut rest = trimmed;
+  let mut total_nanos = 0u128;
+  while !rest.is_empty() {
+    let term_start = leading_space + trimmed.len() - rest.len();
+    let number_len = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+    let (number, after_number) = rest.split_at(number_len);
+    let unit_len = after_number.find(|c: char| c.is_ascii_digit()).unwrap_or(after_number.len());
+    let (unit, after_unit) = after_number.split_at(unit_len);
+    let term = term_start..term_start + number_len + unit_len;
+
+    let unit_nanos = match unit {
+      "µs" => 1_000,
+      unit => UNITS.iter().find(|&&(name, _)| name == unit).ok_or_else(|| term.clone())?.1,
+    };
+    total_nanos = term_nanos(number, unit_nanos)
+      .and_then(|nanos| total_nanos.checked_add(nanos))
+      .ok_or_else(|| term.clone())?;
+    rest = after_unit;
+  }
+
+  let secs = u64::try_from(total_nanos / 1_000_000_000).map_err(|_| 0..s.len())?;
+  Ok(Duration::new(secs, (total_nanos % 1_000_000_000) as u32))
+}
+
+/** The length of `number` units, truncated to whole nanoseconds. */
+fn term_nanos(number: &str, unit_nanos: u128) -> Option<u128> {
+  let (whole, fraction) = match number.find('.') {
+    Some(dot_idx) => (&number[..dot_idx], &number[dot_idx + 1..]),
+    None => (number, ""),
+  };
+  if whole.is_empty() && fraction.is_empty() {
+    return None
+  }
+
+  let whole = if whole.is_empty() { 0 } else { whole.parse::<u128>().ok()? };
+  let mut nanos = whole.checked_mul(unit_nanos)?;
+  let mut scale = unit_nanos;
+  for digit in fraction.chars() {
+    scale /= 10;
+    nanos += digit.to_digit(10)? as u128 * scale;
+  }
+  Some(nanos)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn parse(s: &str) -> Result<Duration, ParseErr> {
+    Duration::parse_from_str(s)
+  }
+
+  #[test]
+  fn durations_parse_with_units() {
+    assert_eq!(parse("1500ms"), Ok(Duration::from_millis(1500)));
+    assert_eq!(parse("30s"), Ok(Duration::from_secs(30)));
+    assert_eq!(parse("2h"), Ok(Duration::from_secs(7200)));
+    assert_eq!(parse("1h30m"), Ok(Duration::from_secs(5400)));
+    assert_eq!(parse("1.5s"), Ok(Duration::from_millis(1500)));
+    assert_eq!(parse("250µs"), Ok(Duration::from_micros(250)));
+    assert_eq!(parse("0"), Ok(Duration::from_secs(0)));
+  }
+
+  #[test]
+  fn malformed_durations_are_rejected() {
+    for s in &["", "30", "s", "30x", "1.2.3s", "-1s", "99999999999999999999999d"] {
+      assert!(parse(s).is_err(), "{} should not parse", s);
+    }
+    let err = parse("5 sec").unwrap_err();
+    assert_eq!(err.to_string(), "invalid duration `5 sec`, expected a number and a unit \
+                                 (d, h, m, s, ms, us, ns), like `1500ms` or `1h30m`".to_owned());
+    assert_eq!(err.span(), 0..5);
+    assert_eq!(parse("1h30x").unwrap_err().span(), 2..5);
+  }
+
+  #[test]
+  fn durations_display_in_the_largest_exact_unit() {
+    let display = |d| Duration::display_value(&d).unwrap();
+
+    assert_eq!(display(Duration::from_millis(1500)), "1500ms".to_owned());
+    assert_eq!(display(Duration::from_secs(7200)), "2h".to_owned());
+    assert_eq!(display(Duration::from_secs(0)), "0s".to_owned());
+    assert_eq!(display(Duration::new(1, 1)), "1000000001ns".to_owned());
+  }
+}
@@ -1,53 +1,119 @@
-#![feature(used)]
-
 #[macro_use]
 extern crate lazy_static;
+extern crate arc_swap;
 
+use std::sync::Arc;
 use std::sync::RwLock;
 use std::ops::Deref;
 use std::convert::From;
+use std::error::Error;
 use std::fmt::Debug;
+use std::fmt::Display;
 use std::fmt::Formatter;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::num::{NonZeroI8, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI128, NonZeroIsize};
+use std::num::{NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU128, NonZeroUsize};
+use std::path::PathBuf;
+
+#[cfg(feature = "regex")]
+extern crate regex;
+
+pub mod validate;
+
+mod collections;
+#[doc(hidden)]
+pub mod derive_support;
+mod duration;
+pub mod list;
+mod parse_err;
+#[doc(hidden)]
+pub mod storage;
+mod units;
+
+pub use parse_err::ParseErr;
+#[doc(hidden)]
+pub use storage::__ConfigValue;
+pub use units::{ByteSize, Percent, Ratio};
+
+#[cfg(feature = "derive")]
+extern crate zcfg_derive;
+#[cfg(feature = "derive")]
+pub use zcfg_derive::ConfigParseable;
+/** Overlays config values for a test. See `zcfg_derive`'s `test_attr` module. */
+#[cfg(feature = "derive")]
+pub use zcfg_derive::test;
 
+/**
+ * Defines a config in a private module named `$name`.
+ *
+ * An optional trailing `validate: [...]` clause lists validators (see the `validate` module) that
+ * the default and every statically set value must pass.
+ *
+ * An optional final `repeated: LastWins` or `repeated: Error` clause sets the config's
+ * `RepeatPolicy`, which decides what a repeated flag does when the config's type does not
+ * accumulate. The default is `Error`.
+ */
 #[macro_export]
 macro_rules! define_cfg {
   ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr) => {
+    define_cfg!($name, $cfg_type, $default_value, $description, validate: [], repeated: Error);
+  };
+  ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr,
+   validate: [$($validator:expr),*]) => {
+    define_cfg!($name, $cfg_type, $default_value, $description, validate: [$($validator),*], repeated: Error);
+  };
+  ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr, repeated: $policy:ident) => {
+    define_cfg!($name, $cfg_type, $default_value, $description, validate: [], repeated: $policy);
+  };
+  ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr,
+   validate: [$($validator:expr),*], repeated: $policy:ident) => {
     #[allow(dead_code)]
     mod $name {
-      _define_config_inner!($name, $cfg_type, $default_value, $description);
+      _define_config_inner!($name, $cfg_type, $default_value, $description, [$($validator),*], $policy);
     }
   };
 }
 
+/** Defines a config in a public module named `$name`. Accepts the same forms as `define_cfg!`. */
 #[macro_export]
 macro_rules! define_pub_cfg {
   ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr) => {
+    define_pub_cfg!($name, $cfg_type, $default_value, $description, validate: [], repeated: Error);
+  };
+  ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr,
+   validate: [$($validator:expr),*]) => {
+    define_pub_cfg!($name, $cfg_type, $default_value, $description, validate: [$($validator),*], repeated: Error);
+  };
+  ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr, repeated: $policy:ident) => {
+    define_pub_cfg!($name, $cfg_type, $default_value, $description, validate: [], repeated: $policy);
+  };
+  ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr,
+   validate: [$($validator:expr),*], repeated: $policy:ident) => {
     #[allow(dead_code)]
     pub mod $name {
-      _define_config_inner!($name, $cfg_type, $default_value, $description);
+      _define_config_inner!($name, $cfg_type, $default_value, $description, [$($validator),*], $policy);
     }
   };
 }
 
 #[macro_export]
 macro_rules! _define_config_inner {
-  ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr) => {
-    use std::sync::Arc;
-    use std::sync::RwLock;
-    use std::ops::Deref;
+  ($name:ident, $cfg_type:ty, $default_value:expr, $description:expr,
+   [$($validator:expr),*], $policy:ident) => {
     use std::convert::Into;
+    #[allow(unused_imports)]
+    use $crate::storage::{AtomicStorage, SharedStorage};
 
     lazy_static! {
       /** The accessible static value for this modules flag. */
       pub static ref CONFIG: $crate::Config<$cfg_type> = {
         $crate::Config::__new_cfg_in_macro_do_not_use_elsewhere(
-          stringify!($name).to_owned(),
-          file!().to_owned(),
-          line!(),
-          $description,
+          __inner_metadata(),
           $default_value.into(),
-          __inner_get,
-          __inner_set_for_testing
+          __inner_value,
         )
       };
 
@@ -56,125 +122,195 @@ macro_rules! _define_config_inner {
        *
        * For general use prefer an aggregate initializer that populates all configs in the binary
        * at once.
-       * For testing, prefer `CONFIG.set_for_testing`.
+       * For testing, prefer `CONFIG.override_for_test`.
        */
       pub static ref INITIALIZER: $crate::ConfigInitializer = {
         $crate::ConfigInitializer::__new_init_in_macro_do_not_use_elsewhere(
-          stringify!($name).to_owned(),
-          file!().to_owned(),
-          line!(),
-          $description,
+          __inner_metadata(),
           __inner_try_set_statically,
+          __inner_overlay_for_test,
+          __inner_get_source,
+          __inner_default_err(),
         )
       };
 
-      static ref _CONFIG_INNER: Arc<RwLock<$crate::__ConfigValue<$cfg_type>>> = {
-        Arc::new(RwLock::new($crate::__ConfigValue::new($default_value.into())))
+      static ref _CONFIG_INNER: $crate::__ConfigValue<$cfg_type> = {
+        (&&$crate::storage::Probe::<$cfg_type>::new()).__new_value($default_value.into())
       };
+
+      static ref _VALIDATORS: Vec<$crate::validate::Validator<$cfg_type>> = vec![
+        $( $crate::validate::__boxed_in_macro_do_not_use_elsewhere($validator) ),*
+      ];
     }
 
-    fn __inner_try_set_statically(s: &str) -> Result<(), $crate::InitErr> {
-      <$cfg_type as $crate::ConfigParseable>::parse_from_str(s)
-        .map_err(|e| $crate::InitErr::FailedToParse(e.to_string()))
-        .and_then(|out| {
-          if !_CONFIG_INNER.write().expect("somebody soiled a config").initialize(out) {
-            Err($crate::InitErr::AlreadyInitOnce)
-          } else {
-            Ok(())
-          }
-        })
+    fn __inner_validate(value: &$cfg_type) -> Result<(), String> {
+      _VALIDATORS.iter().try_for_each(|validator| validator(value))
     }
 
-    fn __inner_get() -> $cfg_type {
-      _CONFIG_INNER.deref().read().expect("somebody soiled a config").get()
+    fn __inner_parse(s: &str) -> Result<$cfg_type, $crate::InitErr> {
+      let out = <$cfg_type as $crate::ConfigParseable>::parse_from_str(s)
+        .map_err(|err| $crate::InitErr::FailedToParse {
+          config_name: stringify!($name).to_owned(),
+          type_name: ::std::any::type_name::<$cfg_type>().to_owned(),
+          err,
+        })?;
+      __inner_validate(&out).map_err(|err| $crate::InitErr::ValidationFailed {
+        config_name: stringify!($name).to_owned(),
+        err,
+      })?;
+      Ok(out)
     }
 
-    fn __inner_set_for_testing(v: $cfg_type) {
-      _CONFIG_INNER.deref().write().expect("somebody soiled a config").set_raw(v)
+    fn __inner_try_set_statically(s: &str, source: $crate::ValueSource) -> Result<(), $crate::InitErr> {
+      let out = __inner_parse(s)?;
+      if !_CONFIG_INNER.initialize(out, source) {
+        Err($crate::InitErr::AlreadyInitOnce)
+      } else {
+        Ok(())
+      }
     }
 
+    fn __inner_overlay_for_test(s: &str) -> Result<$crate::OverlayGuard, $crate::InitErr> {
+      Ok(_CONFIG_INNER.begin_overlay(__inner_parse(s)?))
+    }
 
+    fn __inner_metadata() -> $crate::ConfigMetadata {
+      $crate::ConfigMetadata::new(
+        stringify!($name).to_owned(),
+        file!().to_owned(),
+        line!(),
+        $description,
+        ::std::any::type_name::<$cfg_type>(),
+        __inner_default_as_string(),
+        <$cfg_type as $crate::ConfigParseable>::is_switch(),
+      ).accumulating(<$cfg_type as $crate::ConfigParseable>::accumulates())
+        .on_repeat($crate::RepeatPolicy::$policy)
+    }
+
+    fn __inner_default_as_string() -> Option<String> {
+      let default_value: $cfg_type = $default_value.into();
+      <$cfg_type as $crate::ConfigParseable>::display_value(&default_value)
+    }
+
+    fn __inner_value() -> &'static $crate::__ConfigValue<$cfg_type> {
+      &_CONFIG_INNER
+    }
+
+    fn __inner_get_source() -> $crate::ValueSource {
+      _CONFIG_INNER.source()
+    }
+
+    fn __inner_default_err() -> Option<$crate::InitErr> {
+      let default_value: $cfg_type = $default_value.into();
+      __inner_validate(&default_value).err().map(|err| $crate::InitErr::ValidationFailed {
+        config_name: stringify!($name).to_owned(),
+        err,
+      })
+    }
+
+    // This runs before `main`, where a panic can't unwind, so an invalid default is recorded for
+    // the parsers to report rather than raised here.
     extern "C" fn enqueue_static_config_init() {
       $crate::STATIC_CONFIG_INITIALIZERS.write()
         .unwrap()
         .push($crate::ConfigInitializer::__new_init_in_macro_do_not_use_elsewhere(
-          stringify!($name).to_owned(),
-          file!().to_owned(),
-          line!(),
-          $description,
+          __inner_metadata(),
           __inner_try_set_statically,
+          __inner_overlay_for_test,
+          __inner_get_source,
+          __inner_default_err(),
         ))
     }
 
+    /**
+     * A platform constructor entry that enqueues this config's initializer before `main`.
+     *
+     * `#[used]` (stable since Rust 1.30) keeps the entry alive even though nothing references it.
+     */
     #[used]
-    #[cfg_attr(target_os = "linux", link_section = ".init_array")]
-    #[cfg_attr(target_os = "macos", link_section = "__DATA,__mod_init_func")]
+    #[cfg_attr(any(target_os = "linux",
+                   target_os = "android",
+                   target_os = "freebsd",
+                   target_os = "dragonfly",
+                   target_os = "netbsd",
+                   target_os = "openbsd",
+                   target_os = "illumos"),
+               link_section = ".init_array")]
+    #[cfg_attr(any(target_os = "macos", target_os = "ios"), link_section = "__DATA,__mod_init_func")]
     #[cfg_attr(target_os = "windows", link_section = ".CRT$XCU")]
     #[allow(dead_code)]
     static INIT_ARRAY: [extern "C" fn(); 1] = [enqueue_static_config_init];
   }
 }
 
-define_pub_cfg!(__zcfg_example_pub_config, super::NoneableCfg<String>, None,
-            "A fake pub example configuration object to demo rustdoc for config objects. The \
-            naming convention is completely artificial, and you can choose any convention you'd \
-            like. Prefer distinct names that probably won't conflict with other libraries.");
-define_cfg!(__zcfg_example_priv_config, super::NoneableCfg<String>, None,
-            "A fake example configuration object to demo rustdoc for config objects. The \
-            naming convention is completely artificial, and you can choose any convention you'd \
-            like. Prefer distinct names that probably won't conflict with other libraries.");
+// Declared after the macros above, which their tests use.
+pub mod loader;
+mod overlay;
+pub use loader::{apply_candidates, Candidate, ConfigLoader, ConfigSource, LoadErr, LoadReport, Shadowed};
+pub use overlay::{overlay_candidate, spawn_with_overlays, OverlayGuard, Overlays};
+
+// The example configs keep using the deprecated `NoneableCfg` to show that it is still supported.
+#[allow(deprecated)]
+mod examples {
+  define_pub_cfg!(__zcfg_example_pub_config, ::NoneableCfg<String>, None,
+              "A fake pub example configuration object to demo rustdoc for config objects. The \
+              naming convention is completely artificial, and you can choose any convention you'd \
+              like. Prefer distinct names that probably won't conflict with other libraries.");
+  define_cfg!(__zcfg_example_priv_config, ::NoneableCfg<String>, None,
+              "A fake example configuration object to demo rustdoc for config objects. The \
+              naming convention is completely artificial, and you can choose any convention you'd \
+              like. Prefer distinct names that probably won't conflict with other libraries.");
+}
+pub use examples::__zcfg_example_pub_config;
 
 /** A configurable element in the associated module */
-pub struct Config<T: Clone> {
-  name: String,
-  file_name: String,
-  line_number: u32,
-  description: &'static str,
+pub struct Config<T: Clone + 'static> {
+  metadata: ConfigMetadata,
   default_value: T,
-  _inner_get_value: fn() -> T,
-  _inner_set_for_testing: fn(T),
+  _inner_value: fn() -> &'static __ConfigValue<T>,
 }
 
-impl<T: Clone> Config<T> {
+impl<T: Clone + 'static> Config<T> {
   /**
    * Constructs a new config object for the known static initializer retrieval methods.
    *
    * This is public by necessity for access by the `declare_cfg` macros.
    */
   pub fn __new_cfg_in_macro_do_not_use_elsewhere(
-      name: String,
-      file_name: String,
-      line_number: u32,
-      description: &'static str,
+      metadata: ConfigMetadata,
       default_value: T,
-      get_value: fn() -> T,
-      set_for_testing: fn(T)) 
+      value: fn() -> &'static __ConfigValue<T>)
       -> Config<T> {
     Config {
-      name: name,
-      file_name: file_name,
-      line_number: line_number,
-      description: description,
-      default_value: default_value,
-      _inner_get_value: get_value,
-      _inner_set_for_testing: set_for_testing,
+      metadata,
+      default_value,
+      _inner_value: value,
     }
   }
 
   pub fn name(&self) -> &str {
-    &self.name
+    self.metadata.config_name()
   }
 
   pub fn file_name(&self) -> &str {
-    &self.file_name
+    self.metadata.file()
   }
 
   pub fn line_number(&self) -> u32 {
-    self.line_number
+    self.metadata.line()
   }
 
   pub fn description(&self) -> &str {
-    self.description
+    self.metadata.description()
+  }
+
+  pub fn metadata(&self) -> &ConfigMetadata {
+    &self.metadata
+  }
+
+  /** Where the current value of this config came from. */
+  pub fn source(&self) -> ValueSource {
+    (self._inner_value)().source()
   }
 
   /**
@@ -186,60 +322,199 @@ impl<T: Clone> Config<T> {
    * dependency resolution area of your code, rather than within business logic.
    */
   pub fn get_value(&self) -> T {
-    (self._inner_get_value)()
+    (self._inner_value)().get()
+  }
+
+  /**
+   * Calls `f` with a reference to the current value for this config, without cloning it.
+   *
+   * Prefer this to `get_value` for large values like lists and maps. The value is a snapshot: a
+   * concurrent `set_for_testing` is not seen until the next call.
+   */
+  pub fn with_value<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
+    (self._inner_value)().with(f)
+  }
+
+  /**
+   * Fetch a shared snapshot of the current value for this config, without cloning it.
+   *
+   * The snapshot may be held across later updates, which it will not reflect. Primitive configs
+   * are not stored behind an `Arc`, so this allocates a new one for them.
+   */
+  pub fn get_arc(&self) -> Arc<T> {
+    (self._inner_value)().get_arc()
   }
 
   /**
    * Sets the value of this config directly for testing.
    *
-   * Concurrent tests that invoke this method will probably be flakey. Prefer to set this in a
-   * std::sync::ONCE block, and verify that it has been called.
+   * Concurrent tests that invoke this method will probably be flakey. Prefer `overlay_for_test`,
+   * which only affects the current thread.
    */
   pub fn set_for_testing(&self, v: T) {
-    (self._inner_set_for_testing)(v)
+    (self._inner_value)().set_raw(v, ValueSource::Testing)
   }
 
   /**
    * Resets the value of this config back to its default directly for testing.
    *
-   * Concurrent tests that invoke this method will probably be flakey. Prefer to set this in a
-   * std::sync::ONCE block, and verify that it has been called.
+   * Concurrent tests that invoke this method will probably be flakey. Prefer `overlay_for_test`,
+   * which only affects the current thread.
    */
   pub fn reset_for_testing(&self) {
-    (self._inner_set_for_testing)(self.default_value.clone())
+    (self._inner_value)().set_raw(self.default_value.clone(), ValueSource::Default)
+  }
+
+  /**
+   * Sets the value of this config for testing until the returned guard is dropped.
+   *
+   * Dropping the guard, including while unwinding from a failed assertion, restores the previous
+   * value, its source, and whether the config had been initialized. Overrides of the same config
+   * may be nested, and unwind to the state before the outermost one.
+   *
+   * The value is still global, so concurrent tests that override the same config will interfere.
+   * Prefer `overlay_for_test` for tests that run in parallel.
+   */
+  pub fn override_for_test(&self, v: T) -> OverrideGuard<T> {
+    let value = (self._inner_value)();
+    OverrideGuard {
+      id: value.begin_override(v),
+      value,
+    }
+  }
+
+  /**
+   * Sets the value of this config for the current thread only, until the returned guard is dropped.
+   *
+   * Other threads, including other tests, keep reading the global value, so tests that overlay
+   * the same config may run in parallel. Overlays nest, and shadow `set_for_testing` and
+   * `override_for_test` on this thread. Use `spawn_with_overlays` or `Overlays` to make them visible
+   * to threads that the test starts.
+   */
+  pub fn overlay_for_test(&self, v: T) -> OverlayGuard where T: Send + Sync {
+    (self._inner_value)().begin_overlay(v)
   }
 }
 
-pub type ParseErr = String;
+/** Restores a config's previous value when dropped. See `Config::override_for_test`. */
+#[must_use = "the override ends as soon as the guard is dropped"]
+pub struct OverrideGuard<T: Clone + 'static> {
+  value: &'static __ConfigValue<T>,
+  id: usize,
+}
+
+impl<T: Clone + 'static> Drop for OverrideGuard<T> {
+  fn drop(&mut self) {
+    self.value.end_override(self.id)
+  }
+}
 
 /** Any standard type which may be parsed from a string */
 pub trait ConfigParseable {
   type Output;
   fn parse_from_str(s: &str) -> Result<Self::Output, ParseErr>;
+
+  /**
+   * Renders a value back into a string that `parse_from_str` would accept.
+   *
+   * This is used to describe default values in help text and metadata. Types that cannot be
+   * rendered may leave the default, which reports nothing.
+   */
+  fn display_value(_value: &Self::Output) -> Option<String> {
+    None
+  }
+
+  /**
+   * Whether the config is a switch, like `bool`.
+   *
+   * Switches are set to "true" when named alone (`--verbose`) and "false" when negated
+   * (`--noverbose`), and never consume a following argument.
+   */
+  fn is_switch() -> bool {
+    false
+  }
+
+  /**
+   * Whether repeated values for the config are combined, like the entries of a map.
+   *
+   * The flag parser joins repeated flags for these configs with `list::concat`, so that
+   * `--weights=a=1 --weights=b=2` is read as `--weights=a=1,b=2`. Repeated flags for other
+   * configs are handled by the config's `RepeatPolicy`.
+   */
+  fn accumulates() -> bool {
+    false
+  }
 }
 
 macro_rules! decl_config_parsable_from_str {
   ($auto_parsable_type:ty) => {
+    decl_config_parsable_from_str!($auto_parsable_type, is_switch: false);
+  };
+  ($auto_parsable_type:ty, is_switch: $is_switch:expr) => {
     impl ConfigParseable for $auto_parsable_type {
       type Output = $auto_parsable_type;
       fn parse_from_str(s: &str) -> Result<Self::Output, ParseErr> {
         use std::str::FromStr;
-        <$auto_parsable_type as FromStr>::from_str(s).map_err(|e| e.to_string())
+        <$auto_parsable_type as FromStr>::from_str(s)
+          .map_err(|e| ParseErr::new(s, e.to_string()).expecting(stringify!($auto_parsable_type)))
+      }
+
+      fn display_value(value: &Self::Output) -> Option<String> {
+        Some(value.to_string())
+      }
+
+      fn is_switch() -> bool {
+        $is_switch
       }
     }
-  }
+  };
 }
 
 decl_config_parsable_from_str!(String);
-decl_config_parsable_from_str!(bool);
+decl_config_parsable_from_str!(bool, is_switch: true);
+decl_config_parsable_from_str!(char);
 decl_config_parsable_from_str!(u8);
+decl_config_parsable_from_str!(u16);
 decl_config_parsable_from_str!(u32);
 decl_config_parsable_from_str!(u64);
+decl_config_parsable_from_str!(u128);
+decl_config_parsable_from_str!(usize);
 decl_config_parsable_from_str!(i8);
+decl_config_parsable_from_str!(i16);
 decl_config_parsable_from_str!(i32);
 decl_config_parsable_from_str!(i64);
+decl_config_parsable_from_str!(i128);
+decl_config_parsable_from_str!(isize);
 decl_config_parsable_from_str!(f32);
 decl_config_parsable_from_str!(f64);
+decl_config_parsable_from_str!(NonZeroU8);
+decl_config_parsable_from_str!(NonZeroU16);
+decl_config_parsable_from_str!(NonZeroU32);
+decl_config_parsable_from_str!(NonZeroU64);
+decl_config_parsable_from_str!(NonZeroU128);
+decl_config_parsable_from_str!(NonZeroUsize);
+decl_config_parsable_from_str!(NonZeroI8);
+decl_config_parsable_from_str!(NonZeroI16);
+decl_config_parsable_from_str!(NonZeroI32);
+decl_config_parsable_from_str!(NonZeroI64);
+decl_config_parsable_from_str!(NonZeroI128);
+decl_config_parsable_from_str!(NonZeroIsize);
+decl_config_parsable_from_str!(IpAddr);
+decl_config_parsable_from_str!(Ipv4Addr);
+decl_config_parsable_from_str!(Ipv6Addr);
+decl_config_parsable_from_str!(SocketAddr);
+
+impl ConfigParseable for PathBuf {
+  type Output = PathBuf;
+  fn parse_from_str(s: &str) -> Result<PathBuf, ParseErr> {
+    Ok(PathBuf::from(s))
+  }
+
+  /** Paths that are not valid unicode cannot be rendered. */
+  fn display_value(value: &PathBuf) -> Option<String> {
+    value.to_str().map(|s| s.to_owned())
+  }
+}
 
 
 
@@ -247,6 +522,7 @@ decl_config_parsable_from_str!(f64);
 #[deprecated(since="0.2.0", note="please use Option directly")]
 pub struct NoneableCfg<T>(pub Option<T>);
 
+#[allow(deprecated)]
 impl <T> NoneableCfg<T> {
   pub fn inner(self) -> Option<T> {
     let NoneableCfg(inner) = self;
@@ -254,28 +530,32 @@ impl <T> NoneableCfg<T> {
   }
 }
 
+#[allow(deprecated)]
 impl <T> Debug for NoneableCfg<T> where T: Debug {
   fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-    let &NoneableCfg(ref inner) = self;
+    let NoneableCfg(inner) = self;
     inner.fmt(f)
   }
 }
 
+#[allow(deprecated)]
 impl <T> Deref for NoneableCfg<T> {
   type Target = Option<T>;
 
   fn deref(&self) -> &Option<T> {
-    let &NoneableCfg(ref inner) = self;
+    let NoneableCfg(inner) = self;
     inner
   }
 }
 
+#[allow(deprecated)]
 impl <T> From<Option<T>> for NoneableCfg<T> {
   fn from(v: Option<T>) -> Self {
     NoneableCfg(v)
   }
 }
 
+#[allow(deprecated)]
 impl <T> ConfigParseable for NoneableCfg<T> where T:ConfigParseable {
   type Output = NoneableCfg<<T as ConfigParseable>::Output>;
   fn parse_from_str(s: &str) -> Result<Self::Output, ParseErr> {
@@ -285,10 +565,26 @@ impl <T> ConfigParseable for NoneableCfg<T> where T:ConfigParseable {
         Ok(None)
       }
       s => {
-        <T as ConfigParseable>::parse_from_str(s).map(|v| Some(v))
+        <T as ConfigParseable>::parse_from_str(s).map(Some)
       }
     };
-    opt_res.map(|v| NoneableCfg(v))
+    opt_res.map(NoneableCfg)
+  }
+
+  fn display_value(value: &Self::Output) -> Option<String> {
+    let NoneableCfg(inner) = value;
+    match *inner {
+      None => Some("None".to_owned()),
+      Some(ref v) => <T as ConfigParseable>::display_value(v),
+    }
+  }
+
+  fn is_switch() -> bool {
+    <T as ConfigParseable>::is_switch()
+  }
+
+  fn accumulates() -> bool {
+    <T as ConfigParseable>::accumulates()
   }
 }
 
@@ -300,9 +596,24 @@ impl <T> ConfigParseable for Option<T> where T:ConfigParseable {
       "None" | "none" => {
         Ok(None)
       }
-      s => <T as ConfigParseable>::parse_from_str(s).map(|v| Some(v))
+      s => <T as ConfigParseable>::parse_from_str(s).map(Some)
+    }
+  }
+
+  fn display_value(value: &Self::Output) -> Option<String> {
+    match *value {
+      None => Some("None".to_owned()),
+      Some(ref v) => <T as ConfigParseable>::display_value(v),
     }
   }
+
+  fn is_switch() -> bool {
+    <T as ConfigParseable>::is_switch()
+  }
+
+  fn accumulates() -> bool {
+    <T as ConfigParseable>::accumulates()
+  }
 }
 
 
@@ -310,6 +621,7 @@ impl <T> ConfigParseable for Option<T> where T:ConfigParseable {
 #[deprecated(since="0.2.0", note="please use Vec directly")]
 pub struct CommaSeparatedCfgs<T>(pub Vec<T>);
 
+#[allow(deprecated)]
 impl <T> CommaSeparatedCfgs<T> {
   pub fn inner(self) -> Vec<T> {
     let CommaSeparatedCfgs(inner) = self;
@@ -317,103 +629,145 @@ impl <T> CommaSeparatedCfgs<T> {
   }
 }
 
+#[allow(deprecated)]
 impl <T> Deref for CommaSeparatedCfgs<T> {
   type Target = Vec<T>;
 
   fn deref(&self) -> &Vec<T> {
-    let &CommaSeparatedCfgs(ref inner) = self;
+    let CommaSeparatedCfgs(inner) = self;
     inner
   }
 }
 
+#[allow(deprecated)]
 impl <T> From<Vec<T>> for CommaSeparatedCfgs<T> {
   fn from(v: Vec<T>) -> Self {
     CommaSeparatedCfgs(v)
   }
 }
 
+#[allow(deprecated)]
 impl <T> Debug for CommaSeparatedCfgs<T> where T: Debug {
   fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-    let &CommaSeparatedCfgs(ref inner) = self;
+    let CommaSeparatedCfgs(inner) = self;
     inner.fmt(f)
   }
 }
 
+#[allow(deprecated)]
 impl <T> ConfigParseable for CommaSeparatedCfgs<T> where T:ConfigParseable {
   type Output = CommaSeparatedCfgs<<T as ConfigParseable>::Output>;
   fn parse_from_str(s: &str) -> Result<Self::Output, ParseErr> {
-    let mut results = Vec::new();
-    for element in s.split(',') {
-      let parsed = <T as ConfigParseable>::parse_from_str(element);
-      if parsed.is_err() {
-        return Err(parsed.err().unwrap())
-      } else {
-        results.push(parsed.ok().unwrap())
-      }
-    }
-    return Ok(CommaSeparatedCfgs(results))
+    <Vec<T> as ConfigParseable>::parse_from_str(s).map(CommaSeparatedCfgs)
+  }
+
+  fn display_value(value: &Self::Output) -> Option<String> {
+    let CommaSeparatedCfgs(inner) = value;
+    <Vec<T> as ConfigParseable>::display_value(inner)
+  }
+
+  fn accumulates() -> bool {
+    true
   }
 }
 
+/** Lists use the syntax described in the `list` module, like `a,"b, c",d` or `[1,2],[3]`. */
 impl <T> ConfigParseable for Vec<T> where T:ConfigParseable {
   type Output = Vec<<T as ConfigParseable>::Output>;
   fn parse_from_str(s: &str) -> Result<Self::Output, ParseErr> {
-    let mut results = Vec::new();
-    for element in s.split(',') {
-      let parsed = <T as ConfigParseable>::parse_from_str(element);
-      if parsed.is_err() {
-        return Err(parsed.err().unwrap())
-      } else {
-        results.push(parsed.ok().unwrap())
-      }
-    }
-    return Ok(results)
+    list::split_spanned(s)?.into_iter()
+      .enumerate()
+      .map(|(idx, (element, span))| {
+        <T as ConfigParseable>::parse_from_str(&element).map_err(|e| list::element_err(s, idx, span, e))
+      })
+      .collect()
   }
-}
 
+  fn display_value(value: &Self::Output) -> Option<String> {
+    value.iter()
+      .map(|v| <T as ConfigParseable>::display_value(v))
+      .collect::<Option<Vec<String>>>()
+      .map(|elements| list::join(&elements))
+  }
 
-/**
- * The inner config value.
- *
- * This value is public to allow access by `define_cfg` macros.
- */
-pub struct __ConfigValue<T: Clone> {
-  value: T,
-  initialized: bool
+  fn accumulates() -> bool {
+    true
+  }
 }
 
-impl<T: Clone> __ConfigValue<T> {
-  pub fn new(default: T) -> __ConfigValue<T> {
-    __ConfigValue {
-      value: default,
-      initialized: false
-    }
-  }
-  pub fn get(&self) -> T {
-    self.value.clone()
-  }
 
-  pub fn set_raw(&mut self, t: T) {
-    self.value = t;
-  }
+/** Where the current value of a config came from. */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ValueSource {
+  /** The config has not been set, and holds its default value. */
+  Default,
+  /** A command line flag. */
+  CommandLine,
+  /** The named environment variable. */
+  Environment(String),
+  /** A config file or flagfile, at the given 1-based line. */
+  File(PathBuf, usize),
+  /** `Config::set_for_testing` or `Config::override_for_test`. */
+  Testing,
+  /** `ConfigInitializer::set_statically`, without a more specific source. */
+  Runtime,
+}
 
-  pub fn initialize(&mut self, t: T) -> bool {
-    if self.initialized {
-      return false
+impl Display for ValueSource {
+  fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+    match *self {
+      ValueSource::Default => write!(f, "default value"),
+      ValueSource::CommandLine => write!(f, "command line flag"),
+      ValueSource::Environment(ref var) => write!(f, "environment variable {}", var),
+      ValueSource::File(ref path, line) => write!(f, "{}:{}", path.display(), line),
+      ValueSource::Testing => write!(f, "set_for_testing"),
+      ValueSource::Runtime => write!(f, "runtime update"),
     }
-
-    self.set_raw(t);
-    self.initialized = true;
-    true
   }
 }
 
+/** What a repeated flag does to a config whose type does not accumulate repeated values. */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RepeatPolicy {
+  /** The last occurrence is used, and earlier ones are ignored. */
+  LastWins,
+  /** Repeating the flag is an error. */
+  Error,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum InitErr {
   AlreadyInitOnce,
-  FailedToParse(String),
+  /** The value could not be parsed as the config's type. */
+  FailedToParse {
+    config_name: String,
+    /** The full name of the config's type, as reported by `std::any::type_name`. */
+    type_name: String,
+    err: ParseErr,
+  },
+  /** The value parsed, but was rejected by one of the config's validators. */
+  ValidationFailed {
+    config_name: String,
+    err: String,
+  },
 }
 
+impl Display for InitErr {
+  fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+    match *self {
+      InitErr::AlreadyInitOnce => write!(f, "config was already initialized"),
+      InitErr::FailedToParse { ref config_name, ref err, .. } => {
+        write!(f, "failed to parse value for `{}`: {}", config_name, err)
+      },
+      InitErr::ValidationFailed { ref config_name, ref err } => {
+        write!(f, "value for `{}` failed validation: {}", config_name, err)
+      },
+    }
+  }
+}
+
+impl Error for InitErr {}
+
 /**
  * An initialization object that can statically set config objects.
  *
@@ -422,12 +776,15 @@ pub enum InitErr {
  *
  * You will find an initializer available in the declared config module, named "INITIALIZER".
  * For the purpose of testing, prefer to access the config value directly (named "CONFIG"), and
- * invoke `.set_for_testing` directly.
+ * invoke `.override_for_test` directly.
  * For general application initialization, prefer a global config intializer invoked in main.
  */
 pub struct ConfigInitializer {
   metadata: ConfigMetadata,
-  internal_set_statically: fn(&str) -> Result<(), InitErr>,
+  internal_set_statically: fn(&str, ValueSource) -> Result<(), InitErr>,
+  internal_overlay_for_test: fn(&str) -> Result<OverlayGuard, InitErr>,
+  internal_get_source: fn() -> ValueSource,
+  default_err: Option<InitErr>,
 }
 
 impl ConfigInitializer {
@@ -437,15 +794,18 @@ impl ConfigInitializer {
    * This function is public to allow access by `define_cfg` macros.
    */
   pub fn __new_init_in_macro_do_not_use_elsewhere(
-      name: String,
-      file_name: String,
-      line_number: u32,
-      description: &'static str,
-      initialize: fn(&str) -> Result<(), InitErr>)
+      metadata: ConfigMetadata,
+      initialize: fn(&str, ValueSource) -> Result<(), InitErr>,
+      overlay_for_test: fn(&str) -> Result<OverlayGuard, InitErr>,
+      get_source: fn() -> ValueSource,
+      default_err: Option<InitErr>)
       -> ConfigInitializer {
     ConfigInitializer {
-      metadata: ConfigMetadata::new(name, file_name, line_number, description),
+      metadata,
       internal_set_statically: initialize,
+      internal_overlay_for_test: overlay_for_test,
+      internal_get_source: get_source,
+      default_err,
     }
   }
 
@@ -465,10 +825,35 @@ impl ConfigInitializer {
     self.metadata.description()
   }
 
+  pub fn type_name(&self) -> &str {
+    self.metadata.type_name()
+  }
+
+  pub fn default_value(&self) -> Option<&str> {
+    self.metadata.default_value()
+  }
+
+  pub fn is_switch(&self) -> bool {
+    self.metadata.is_switch()
+  }
+
+  pub fn accumulates(&self) -> bool {
+    self.metadata.accumulates()
+  }
+
+  pub fn repeat_policy(&self) -> RepeatPolicy {
+    self.metadata.repeat_policy()
+  }
+
   pub fn metadata(&self) -> &ConfigMetadata {
     &self.metadata
   }
 
+  /** Why the config's default value failed its validators, if it did. */
+  pub fn default_err(&self) -> Option<&InitErr> {
+    self.default_err.as_ref()
+  }
+
   /**
    * Attempts to parse the config from a string and load it into the associated Config value.
    *
@@ -476,7 +861,30 @@ impl ConfigInitializer {
    * typically command line arguments or a config file, to populate Config values.
    */
   pub fn set_statically(&self, s: &str) -> Result<(), InitErr> {
-    (self.internal_set_statically)(s)
+    self.set_statically_from(s, ValueSource::Runtime)
+  }
+
+  /**
+   * Like `set_statically`, also recording where the value came from.
+   *
+   * Config resolution crates should use this so that `source` can explain a config's value.
+   */
+  pub fn set_statically_from(&self, s: &str, source: ValueSource) -> Result<(), InitErr> {
+    (self.internal_set_statically)(s, source)
+  }
+
+  /**
+   * Parses and validates a value for the associated Config, and overlays it on the current thread.
+   *
+   * See `Config::overlay_for_test`. The config's initialized state is not affected.
+   */
+  pub fn overlay_for_test(&self, s: &str) -> Result<OverlayGuard, InitErr> {
+    (self.internal_overlay_for_test)(s)
+  }
+
+  /** Where the current value of the associated Config came from. */
+  pub fn source(&self) -> ValueSource {
+    (self.internal_get_source)()
   }
 }
 
@@ -486,6 +894,11 @@ pub struct ConfigMetadata {
   file_name: String,
   line_number: u32,
   description: &'static str,
+  type_name: &'static str,
+  default_value: Option<String>,
+  is_switch: bool,
+  accumulates: bool,
+  repeat_policy: RepeatPolicy,
 }
 
 impl ConfigMetadata {
@@ -493,16 +906,36 @@ impl ConfigMetadata {
       config_name: String,
       file_name: String,
       line_number: u32,
-      description: &'static str)
+      description: &'static str,
+      type_name: &'static str,
+      default_value: Option<String>,
+      is_switch: bool)
       -> ConfigMetadata {
     ConfigMetadata {
-      config_name: config_name,
-      file_name: file_name,
-      line_number: line_number,
-      description: description
+      config_name,
+      file_name,
+      line_number,
+      description,
+      type_name,
+      default_value,
+      is_switch,
+      accumulates: false,
+      repeat_policy: RepeatPolicy::Error,
     }
   }
 
+  /** Marks the config as accumulating repeated values. Configs don't accumulate by default. */
+  pub fn accumulating(mut self, accumulates: bool) -> ConfigMetadata {
+    self.accumulates = accumulates;
+    self
+  }
+
+  /** Sets what a repeated flag does to the config. Defaults to `RepeatPolicy::Error`. */
+  pub fn on_repeat(mut self, repeat_policy: RepeatPolicy) -> ConfigMetadata {
+    self.repeat_policy = repeat_policy;
+    self
+  }
+
   pub fn config_name(&self) -> &str {
     &self.config_name
   }
@@ -518,6 +951,35 @@ impl ConfigMetadata {
   pub fn description(&self) -> &str {
     self.description
   }
+
+  /** The full name of the config's type, as reported by `std::any::type_name`. */
+  pub fn type_name(&self) -> &str {
+    self.type_name
+  }
+
+  /**
+   * The config's default value, rendered by `ConfigParseable::display_value`.
+   *
+   * This is `None` if the config's type does not support rendering its values.
+   */
+  pub fn default_value(&self) -> Option<&str> {
+    self.default_value.as_deref()
+  }
+
+  /** Whether the config is a switch. See `ConfigParseable::is_switch`. */
+  pub fn is_switch(&self) -> bool {
+    self.is_switch
+  }
+
+  /** Whether the config accumulates repeated values. See `ConfigParseable::accumulates`. */
+  pub fn accumulates(&self) -> bool {
+    self.accumulates
+  }
+
+  /** What a repeated flag does to the config, if it does not accumulate. */
+  pub fn repeat_policy(&self) -> RepeatPolicy {
+    self.repeat_policy
+  }
 }
 
 lazy_static! {
@@ -532,15 +994,43 @@ lazy_static! {
   };
 }
 
+/**
+ * The validation errors of every linked config whose default value fails its validators.
+ *
+ * A binary with such a config is misconfigured, so the parsers report these before applying any
+ * values.
+ */
+pub fn invalid_defaults() -> Vec<InitErr> {
+  STATIC_CONFIG_INITIALIZERS.read()
+    .expect("initializers were poisoned")
+    .iter()
+    .filter_map(|initializer| initializer.default_err().cloned())
+    .collect()
+}
+
 #[cfg(test)]
 mod test {
   pub use super::*;
+  // Shadows `zcfg::test` from the glob import above, when the `derive` feature is enabled.
+  #[allow(unused_imports)]
+  use std::prelude::v1::test;
   define_cfg!(example_1, String, "hello".to_owned(), "some example configuration");
   define_cfg!(example_2, u32, 5u32, "some example_2 configuration");
-  define_pub_cfg!(example_3, super::NoneableCfg<String>, None, "some example_3 configuration");
-  define_pub_cfg!(example_4, super::NoneableCfg<u32>, None, "some example_4 configuration");
+  #[allow(deprecated)]
+  mod deprecated_wrappers {
+    define_pub_cfg!(example_3, ::NoneableCfg<String>, None, "some example_3 configuration");
+    define_pub_cfg!(example_4, ::NoneableCfg<u32>, None, "some example_4 configuration");
+  }
+  pub use self::deprecated_wrappers::{example_3, example_4};
   define_pub_cfg!(example_5, Option<String>, None, "some example_5 configuration");
   define_pub_cfg!(example_6, Vec<u32>, Vec::new(), "some example_6 configuration");
+  define_cfg!(example_7, i32, 5, "some example_7 configuration",
+              validate: [::validate::range(0, 10), super::not_seven]);
+  define_cfg!(example_8, u32, 0u32, "some example_8 configuration");
+  define_cfg!(example_9, i32, 5, "some example_9 configuration",
+              validate: [::validate::range(0, 10)], repeated: LastWins);
+  define_cfg!(example_10, u32, 0u32, "some example_10 configuration",
+              validate: [super::counted_validator()]);
   use self::example_1::CONFIG as CONFIG_example_1;
   use self::example_2::CONFIG as CONFIG_example_2;
   use self::example_3::CONFIG as CONFIG_example_3;
@@ -548,23 +1038,28 @@ mod test {
   use self::example_5::CONFIG as CONFIG_example_5;
   use self::example_6::CONFIG as CONFIG_example_6;
   use std::sync::Mutex;
+  use std::sync::atomic::{AtomicUsize, Ordering};
 
-  lazy_static! {
-    static ref NO_TEST_PARALLELISM: Mutex<()>= { Mutex::new(()) };
+  fn not_seven(v: &i32) -> Result<(), String> {
+    if *v == 7 { Err("seven is right out".to_owned()) } else { Ok(()) }
   }
 
-  fn reset_world() {
-    CONFIG_example_1.reset_for_testing();
-    CONFIG_example_2.reset_for_testing();
-    CONFIG_example_3.reset_for_testing();
-    CONFIG_example_4.reset_for_testing();
+  static VALIDATORS_BUILT: AtomicUsize = AtomicUsize::new(0);
+
+  fn counted_validator() -> impl Fn(&u32) -> Result<(), String> {
+    VALIDATORS_BUILT.fetch_add(1, Ordering::SeqCst);
+    |_| Ok(())
+  }
+
+  lazy_static! {
+    static ref NO_TEST_PARALLELISM: Mutex<()>= Mutex::new(());
   }
 
   #[test]
   fn set_for_testing_works() {
     #[allow(unused_variables)]
     let l = NO_TEST_PARALLELISM.lock();
-    reset_world();
+    let _restore = CONFIG_example_1.override_for_test("hello".to_owned());
 
     CONFIG_example_1.set_for_testing("goodbye".to_owned());
     assert_eq!(CONFIG_example_1.get_value(), "goodbye".to_owned());
@@ -574,8 +1069,7 @@ mod test {
   fn reset_for_testing_works() {
     #[allow(unused_variables)]
     let l = NO_TEST_PARALLELISM.lock();
-    // A little suspicious -- we're relying on resetting to work for the reset test
-    reset_world();
+    let _restore = CONFIG_example_1.override_for_test("hello".to_owned());
 
     CONFIG_example_1.set_for_testing("goodbye".to_owned());
     assert_eq!(CONFIG_example_1.get_value(), "goodbye".to_owned());
@@ -587,7 +1081,6 @@ mod test {
   fn unset_flags_are_unset() {
     #[allow(unused_variables)]
     let l = NO_TEST_PARALLELISM.lock();
-    reset_world();
 
     assert_eq!(CONFIG_example_3.get_value().inner(), None);
     assert_eq!(CONFIG_example_4.get_value().inner(), None);
@@ -597,17 +1090,103 @@ mod test {
   fn basic_collections_work() {
     #[allow(unused_variables)]
     let l = NO_TEST_PARALLELISM.lock();
-    reset_world();
 
     assert_eq!(CONFIG_example_5.get_value(), None);
     assert_eq!(CONFIG_example_6.get_value(), Vec::new());
   }
 
+  #[test]
+  fn values_can_be_borrowed() {
+    #[allow(unused_variables)]
+    let l = NO_TEST_PARALLELISM.lock();
+    let _restore = CONFIG_example_6.override_for_test(vec![1, 2, 3]);
+
+    assert_eq!(CONFIG_example_6.with_value(|v| v.iter().sum::<u32>()), 6);
+    let snapshot = CONFIG_example_6.get_arc();
+    let _inner = CONFIG_example_6.override_for_test(vec![4]);
+    assert_eq!((&*snapshot, &*CONFIG_example_6.get_arc()), (&vec![1, 2, 3], &vec![4]));
+    assert_eq!(CONFIG_example_2.with_value(|v| *v), 5);
+    assert_eq!(*CONFIG_example_2.get_arc(), 5);
+  }
+
+  #[test]
+  fn overrides_unwind_in_any_order() {
+    #[allow(unused_variables)]
+    let l = NO_TEST_PARALLELISM.lock();
+
+    {
+      let _outer = CONFIG_example_2.override_for_test(6);
+      {
+        let _inner = CONFIG_example_2.override_for_test(7);
+        assert_eq!((CONFIG_example_2.get_value(), CONFIG_example_2.source()), (7, ValueSource::Testing));
+      }
+      assert_eq!(CONFIG_example_2.get_value(), 6);
+    }
+    assert_eq!((CONFIG_example_2.get_value(), CONFIG_example_2.source()), (5, ValueSource::Default));
+
+    let outer = CONFIG_example_2.override_for_test(1);
+    let inner = CONFIG_example_2.override_for_test(2);
+    drop(outer);
+    assert_eq!(CONFIG_example_2.get_value(), 2);
+    drop(inner);
+    assert_eq!(CONFIG_example_2.get_value(), 5);
+
+    let panicked = ::std::panic::catch_unwind(|| {
+      let _restore = CONFIG_example_2.override_for_test(9);
+      panic!("a failing test");
+    });
+    assert!(panicked.is_err());
+    assert_eq!(CONFIG_example_2.get_value(), 5);
+  }
+
+  #[test]
+  fn overrides_restore_the_initialized_state() {
+    #[allow(unused_variables)]
+    let l = NO_TEST_PARALLELISM.lock();
+
+    for value in &["2", "3"] {
+      let _restore = example_8::CONFIG.override_for_test(1);
+      assert_eq!(example_8::INITIALIZER.set_statically(value), Ok(()));
+      assert_eq!(example_8::INITIALIZER.set_statically(value), Err(InitErr::AlreadyInitOnce));
+    }
+    assert_eq!((example_8::CONFIG.get_value(), example_8::CONFIG.source()), (0, ValueSource::Default));
+  }
+
+  #[test]
+  fn std_types_parse_and_display() {
+    assert_eq!(u16::parse_from_str("65535"), Ok(65535));
+    assert!(u16::parse_from_str("65536").is_err());
+    assert_eq!(char::parse_from_str("x"), Ok('x'));
+    assert_eq!(NonZeroU32::parse_from_str("3"), Ok(NonZeroU32::new(3).unwrap()));
+    assert_eq!(NonZeroU32::parse_from_str("0").unwrap_err().to_string(),
+               "number would be zero for non-zero type, expected NonZeroU32");
+    assert_eq!(SocketAddr::parse_from_str("127.0.0.1:8080"),
+               Ok(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080)));
+    assert_eq!(IpAddr::display_value(&IpAddr::V6(Ipv6Addr::LOCALHOST)), Some("::1".to_owned()));
+    assert_eq!(PathBuf::parse_from_str("/etc/app.toml"), Ok(PathBuf::from("/etc/app.toml")));
+    assert_eq!(PathBuf::display_value(&PathBuf::from("/etc/app.toml")), Some("/etc/app.toml".to_owned()));
+  }
+
+  #[test]
+  fn lists_nest_and_report_bad_elements() {
+    assert_eq!(Vec::<Vec<u32>>::parse_from_str("[1,2],[3],[]"), Ok(vec![vec![1, 2], vec![3], vec![]]));
+    assert_eq!(Vec::<u32>::parse_from_str(""), Ok(vec![]));
+    assert_eq!(Vec::<u32>::parse_from_str("1,x").unwrap_err().to_string(),
+               "list element 1: invalid digit found in string, expected u32");
+    let err = Vec::<Vec<u32>>::parse_from_str("[1],[2,x]").unwrap_err();
+    assert_eq!(err.to_string(), "list element 1: list element 1: invalid digit found in string, expected u32");
+    assert_eq!(err.span(), 4..9);
+    assert_eq!(err.cause().and_then(|e| e.cause()).map(|e| e.input()), Some("x"));
+
+    let nested = vec![vec!["a,b".to_owned()], vec![], vec!["".to_owned(), "c".to_owned()]];
+    let displayed = Vec::<Vec<String>>::display_value(&nested).unwrap();
+    assert_eq!(Vec::<Vec<String>>::parse_from_str(&displayed), Ok(nested));
+  }
+
   #[test]
   fn global_initializer_contains_all_flags() {
     #[allow(unused_variables)]
     let l = NO_TEST_PARALLELISM.lock();
-    reset_world();
 
     let mut static_config_names = STATIC_CONFIG_INITIALIZERS.read()
       .expect("some other test ruined the initializer")
@@ -621,13 +1200,115 @@ mod test {
       "__zcfg_example_priv_config".to_owned(),
       "__zcfg_example_pub_config".to_owned(),
       "example_1".to_owned(),
+      "example_10".to_owned(),
       "example_2".to_owned(),
       "example_3".to_owned(),
       "example_4".to_owned(),
       "example_5".to_owned(),
       "example_6".to_owned(),
+      "example_7".to_owned(),
+      "example_8".to_owned(),
+      "example_9".to_owned(),
+      "loader_1".to_owned(),
+      "loader_2".to_owned(),
+      "loader_3".to_owned(),
+      "loader_4".to_owned(),
+      "loader_6".to_owned(),
+      "loader_7".to_owned(),
+      "overlay_1".to_owned(),
+      "overlay_2".to_owned(),
+      "overlay_3".to_owned(),
+      "overlay_4".to_owned(),
     ];
 
     assert_eq!(static_config_names, expected_values);
   }
+
+  #[test]
+  fn global_initializer_records_type_and_default() {
+    #[allow(unused_variables)]
+    let l = NO_TEST_PARALLELISM.lock();
+
+    let initializers = STATIC_CONFIG_INITIALIZERS.read()
+      .expect("some other test ruined the initializer");
+    let find = |name: &str| initializers.iter()
+      .find(|initializer| initializer.config_name() == name)
+      .unwrap()
+      .metadata()
+      .clone();
+
+    assert_eq!(find("example_1").type_name(), "alloc::string::String");
+    assert_eq!(find("example_1").default_value(), Some("hello"));
+    assert_eq!(find("example_2").type_name(), "u32");
+    assert_eq!(find("example_2").default_value(), Some("5"));
+    assert_eq!(find("example_4").default_value(), Some("None"));
+    assert_eq!(find("example_6").default_value(), Some(""));
+  }
+
+  #[test]
+  fn validators_reject_bad_values() {
+    #[allow(unused_variables)]
+    let l = NO_TEST_PARALLELISM.lock();
+    let _restore = example_7::CONFIG.override_for_test(5);
+
+    assert_eq!(example_7::INITIALIZER.set_statically("-1"),
+               Err(InitErr::ValidationFailed {
+                 config_name: "example_7".to_owned(),
+                 err: "-1 is outside the range [0, 10]".to_owned(),
+               }));
+    assert_eq!(example_7::INITIALIZER.set_statically("7"),
+               Err(InitErr::ValidationFailed {
+                 config_name: "example_7".to_owned(),
+                 err: "seven is right out".to_owned(),
+               }));
+    assert_eq!(example_7::CONFIG.get_value(), 5);
+    assert_eq!(example_7::INITIALIZER.set_statically("3"), Ok(()));
+    assert_eq!(example_7::CONFIG.get_value(), 3);
+  }
+
+  #[test]
+  fn sources_are_recorded() {
+    #[allow(unused_variables)]
+    let l = NO_TEST_PARALLELISM.lock();
+    let _restore = example_8::CONFIG.override_for_test(0);
+
+    assert_eq!(CONFIG_example_1.source(), ValueSource::Default);
+    CONFIG_example_1.set_for_testing("goodbye".to_owned());
+    assert_eq!(CONFIG_example_1.source(), ValueSource::Testing);
+    CONFIG_example_1.reset_for_testing();
+    assert_eq!(CONFIG_example_1.source(), ValueSource::Default);
+
+    let source = ValueSource::File(PathBuf::from("app.toml"), 3);
+    assert_eq!(example_8::INITIALIZER.set_statically_from("4", source.clone()), Ok(()));
+    assert_eq!(example_8::CONFIG.source(), source);
+    assert_eq!(example_8::INITIALIZER.source(), source);
+    assert_eq!(source.to_string(), "app.toml:3".to_owned());
+    assert_eq!(ValueSource::Environment("APP_X".to_owned()).to_string(),
+               "environment variable APP_X".to_owned());
+  }
+
+  #[test]
+  #[allow(deprecated)]
+  fn initializers_describe_repeated_values() {
+    assert!(example_6::INITIALIZER.accumulates());
+    assert!(!example_2::INITIALIZER.accumulates());
+    assert!(<Option<Vec<u32>> as ConfigParseable>::accumulates());
+    assert!(<NoneableCfg<Vec<u32>> as ConfigParseable>::accumulates());
+    assert!(<Option<bool> as ConfigParseable>::is_switch());
+    assert_eq!(example_2::INITIALIZER.repeat_policy(), RepeatPolicy::Error);
+    assert_eq!(example_9::INITIALIZER.repeat_policy(), RepeatPolicy::LastWins);
+    assert_eq!(example_9::INITIALIZER.set_statically("11"),
+               Err(InitErr::ValidationFailed {
+                 config_name: "example_9".to_owned(),
+                 err: "11 is outside the range [0, 10]".to_owned(),
+               }));
+  }
+
+  #[test]
+  fn validators_are_built_once() {
+    for value in &["1", "2", "3"] {
+      let _overlay = example_10::INITIALIZER.overlay_for_test(value).unwrap();
+    }
+    assert_eq!(VALIDATORS_BUILT.load(Ordering::SeqCst), 1);
+  }
 }
@@ -0,0 +1,291 @@
+/*!
+ * The list syntax used by `Vec<T>` configs.
+ *
+ * Elements are separated by commas, as in `a,b,c`. Each element is one of:
+ *
+ * - A bare value, in which `\` escapes the next character, as in `Hello\, World`.
+ * - A double quoted value, in which `\` escapes the next character, as in `"Hello, \"World\""`.
+ * - A bracketed value, which is passed to the element type as-is, as in `[1,2]`. This is how
+ *   lists are nested: `[1,2],[3]` is a `Vec<Vec<u32>>` of two lists.
+ *
+ * An empty value, or `[]`, is the empty list. A list holding one empty string is written `""`.
+ *
+ * Maps use the same syntax, with each element written as an entry `key=value`, as in
+ * `a=1,b=2`. An entry is split at its first `=`, so a key containing `=` is double quoted within
+ * the entry, as in `"\"a=b\"=1"`.
+ */
+
+use std::ops::Range;
+use ParseErr;
+
+/** Splits a list into its elements, resolving quotes and escapes. */
+pub fn split(s: &str) -> Result<Vec<String>, ParseErr> {
+  Ok(split_spanned(s)?.into_iter().map(|(element, _)| element).collect())
+}
+
+/** Like `split`, also returning the byte range that each element occupies in `s`. */
+pub fn split_spanned(s: &str) -> Result<Vec<(String, Range<usize>)>, ParseErr> {
+  if s.is_empty() || s == "[]" {
+    return Ok(Vec::new())
+  }
+
+  let syntax_err = |idx: usize, at: Range<usize>, message: &str| {
+    ParseErr::new(s, format!("list element {}: {}", idx, message)).at(at)
+  };
+  let mut elements = Vec::new();
+  let mut chars = s.char_indices().peekable();
+  loop {
+    let idx = elements.len();
+    let start = chars.peek().map(|&(pos, _)| pos).unwrap_or(s.len());
+    let element = match chars.peek() {
+      Some(&(_, '"')) => {
+        chars.next();
+        let mut element = String::new();
+        loop {
+          match chars.next() {
+            None => return Err(syntax_err(idx, start..s.len(), "unterminated quote")),
+            Some((_, '"')) => break,
+            Some((pos, '\\')) => {
+              element.push(chars.next().ok_or_else(|| syntax_err(idx, pos..s.len(), "trailing `\\`"))?.1)
+            },
+            Some((_, c)) => element.push(c),
+          }
+        }
+        element
+      },
+      Some(&(_, '[')) => {
+        let mut element = String::new();
+        let mut depth = 0usize;
+        let mut in_quotes = false;
+        loop {
+          let (pos, c) = chars.next().ok_or_else(|| syntax_err(idx, start..s.len(), "unterminated `[`"))?;
+          match c {
+            '\\' => {
+              element.push(c);
+              element.push(chars.next().ok_or_else(|| syntax_err(idx, pos..s.len(), "trailing `\\`"))?.1);
+              continue
+            },
+            '"' => in_quotes = !in_quotes,
+            '[' if !in_quotes => depth += 1,
+            ']' if !in_quotes => depth -= 1,
+            _ => {},
+          }
+          element.push(c);
+          if depth == 0 {
+            break
+          }
+        }
+        element[1..element.len() - 1].to_owned()
+      },
+      _ => {
+        let mut element = String::new();
+        while let Some(&(pos, c)) = chars.peek() {
+          if c == ',' {
+            break
+          }
+          chars.next();
+          if c == '\\' {
+            element.push(chars.next().ok_or_else(|| syntax_err(idx, pos..s.len(), "trailing `\\`"))?.1);
+          } else {
+            element.push(c);
+          }
+        }
+        element
+      },
+    };
+    let end = chars.peek().map(|&(pos, _)| pos).unwrap_or(s.len());
+    elements.push((element, start..end));
+
+    match chars.next() {
+      None => return Ok(elements),
+      Some((_, ',')) => {},
+      Some((pos, c)) => {
+        let message = format!("expected `,` after the element, found `{}`", c);
+        return Err(syntax_err(idx, pos..pos + c.len_utf8(), &message))
+      },
+    }
+  }
+}
+
+/** Joins elements into a list that `split` turns back into the same elements. */
+pub fn join<S: AsRef<str>>(elements: &[S]) -> String {
+  if elements.len() == 1 && elements[0].as_ref().is_empty() {
+    return "\"\"".to_owned()
+  }
+  elements.iter()
+    .map(|element| quote_element(element.as_ref()))
+    .collect::<Vec<String>>()
+    .join(",")
+}
+
+/** Quotes an element if it would not otherwise be read back as itself. */
+fn quote_element(element: &str) -> String {
+  let needs_quotes = element.starts_with('"')
+    || element.starts_with('[')
+    || element.contains([',', '\\']);
+  if !needs_quotes {
+    return element.to_owned()
+  }
+  let mut quoted = String::from("\"");
+  for c in element.chars() {
+    if c == '"' || c == '\\' {
+      quoted.push('\\');
+    }
+    quoted.push(c);
+  }
+  quoted.push('"');
+  quoted
+}
+
+/**
+ * Concatenates lists, so that the elements of each follow the elements of those before it.
+ *
+ * This is used to accumulate repeated flags, as in `--weights=a=1 --weights=b=2`.
+ */
+pub fn concat<S: AsRef<str>>(lists: &[S]) -> String {
+  lists.iter()
+    .map(|list| list.as_ref())
+    .filter(|list| !list.is_empty() && *list != "[]")
+    .collect::<Vec<&str>>()
+    .join(",")
+}
+
+/**
+ * Splits a map entry, already taken from its list by `split`, into its key and value.
+ *
+ * The value is a suffix of `entry`, so that it can be located within the entry.
+ */
+pub fn split_entry(entry: &str) -> Result<(String, &str), ParseErr> {
+  let missing_eq = |at: usize| {
+    ParseErr::new(entry, "missing `=`").at(at..entry.len()).expecting("an entry like `key=value`")
+  };
+  if !entry.starts_with('"') {
+    let eq_idx = entry.find('=').ok_or_else(|| missing_eq(entry.len()))?;
+    return Ok((entry[..eq_idx].to_owned(), &entry[eq_idx + 1..]))
+  }
+
+  let mut key = String::new();
+  let mut chars = entry.char_indices().skip(1);
+  loop {
+    match chars.next() {
+      None => return Err(ParseErr::new(entry, "unterminated quote in key")),
+      Some((_, '"')) => break,
+      Some((pos, '\\')) => {
+        key.push(chars.next().ok_or_else(|| ParseErr::new(entry, "trailing `\\` in key").at(pos..entry.len()))?.1)
+      },
+      Some((_, c)) => key.push(c),
+    }
+  }
+  match chars.next() {
+    Some((eq_idx, '=')) => Ok((key, &entry[eq_idx + 1..])),
+    Some((pos, _)) => Err(missing_eq(pos)),
+    None => Err(missing_eq(entry.len())),
+  }
+}
+
+/** Joins a key and value into an entry that `split_entry` turns back into the same pair. */
+pub fn join_entry(key: &str, value: &str) -> String {
+  if !key.starts_with('"') && !key.contains('=') {
+    return format!("{}={}", key, value)
+  }
+  let mut entry = String::from("\"");
+  for c in key.chars() {
+    if c == '"' || c == '\\' {
+      entry.push('\\');
+    }
+    entry.push(c);
+  }
+  format!("{}\"={}", entry, value)
+}
+
+/** An error for the element at `idx`, which occupies `span` of the list `input`. */
+pub(crate) fn element_err(input: &str, idx: usize, span: Range<usize>, cause: ParseErr) -> ParseErr {
+  ParseErr::new(input, format!("list element {}", idx)).at(span).caused_by(cause)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn strings(strs: &[&str]) -> Vec<String> {
+    strs.iter().map(|s| s.to_string()).collect()
+  }
+
+  #[test]
+  fn elements_are_split_on_commas() {
+    assert_eq!(split("a,b, c"), Ok(strings(&["a", "b", " c"])));
+    assert_eq!(split("a,,b"), Ok(strings(&["a", "", "b"])));
+    assert_eq!(split(""), Ok(Vec::new()));
+    assert_eq!(split("[]"), Ok(Vec::new()));
+    assert_eq!(split("\"\""), Ok(strings(&[""])));
+  }
+
+  #[test]
+  fn escapes_and_quotes_protect_commas() {
+    assert_eq!(split("Hello\\, World,Hi"), Ok(strings(&["Hello, World", "Hi"])));
+    assert_eq!(split("\"Hello, \\\"World\\\"\",Hi"), Ok(strings(&["Hello, \"World\"", "Hi"])));
+    assert_eq!(split("C:\\\\path"), Ok(strings(&["C:\\path"])));
+  }
+
+  #[test]
+  fn brackets_nest_lists() {
+    assert_eq!(split("[1,2],[3],[]"), Ok(strings(&["1,2", "3", ""])));
+    assert_eq!(split("[[1],[\"]\"]],x"), Ok(strings(&["[1],[\"]\"]", "x"])));
+  }
+
+  #[test]
+  fn malformed_lists_report_the_element() {
+    let err = |s: &str| {
+      let e = split(s).unwrap_err();
+      (e.to_string(), e.span())
+    };
+
+    assert_eq!(err("a,\"b"), ("list element 1: unterminated quote".to_owned(), 2..4));
+    assert_eq!(err("a,b,[1,2"), ("list element 2: unterminated `[`".to_owned(), 4..8));
+    assert_eq!(err("\"a\"b"), ("list element 0: expected `,` after the element, found `b`".to_owned(), 3..4));
+    assert_eq!(err("a\\"), ("list element 0: trailing `\\`".to_owned(), 1..2));
+  }
+
+  #[test]
+  fn elements_know_their_spans() {
+    assert_eq!(split_spanned("a,\"b,c\",[1]"),
+               Ok(vec![("a".to_owned(), 0..1), ("b,c".to_owned(), 2..7), ("1".to_owned(), 8..11)]));
+  }
+
+  #[test]
+  fn joined_elements_split_back() {
+    let cases: Vec<Vec<String>> = vec![
+      strings(&[]),
+      strings(&[""]),
+      strings(&["", ""]),
+      strings(&["Hello, World", "say \"hi\"", "C:\\path", "[x]", "plain"]),
+    ];
+    for elements in cases {
+      assert_eq!(split(&join(&elements)), Ok(elements.clone()), "{}", join(&elements));
+    }
+  }
+
+  #[test]
+  fn entries_split_at_the_first_eq() {
+    assert_eq!(split_entry("a=1"), Ok(("a".to_owned(), "1")));
+    assert_eq!(split_entry("a=b=c"), Ok(("a".to_owned(), "b=c")));
+    assert_eq!(split_entry("\"a=b\"=c"), Ok(("a=b".to_owned(), "c")));
+    assert_eq!(split_entry("=1"), Ok(("".to_owned(), "1")));
+    assert_eq!(split_entry("a").unwrap_err().to_string(), "missing `=`, expected an entry like `key=value`");
+    assert_eq!(split_entry("\"a\"b=1").unwrap_err().span(), 3..6);
+  }
+
+  #[test]
+  fn joined_entries_split_back() {
+    for &(key, value) in &[("a", "1"), ("a=b", "c=d"), ("\"q\\", ""), ("", "x")] {
+      let entry = join_entry(key, value);
+      assert_eq!(split_entry(&entry), Ok((key.to_owned(), value)), "{}", entry);
+    }
+  }
+
+  #[test]
+  fn lists_concatenate() {
+    assert_eq!(concat(&["a=1", "", "b=2,c=3", "[]"]), "a=1,b=2,c=3".to_owned());
+    assert_eq!(split(&concat(&["\"\"", "x"])), Ok(strings(&["", "x"])));
+  }
+}
@@ -0,0 +1,364 @@
+/*!
+ * Combines config values from several sources, such as a file, the environment and flags.
+ *
+ * Each config can only be initialized once, so applying several parsers one after another fails
+ * as soon as two of them set the same config. A `ConfigLoader` instead collects candidate values
+ * from every source, picks the one with the highest precedence for each config, and commits only
+ * that one.
+ *
+ * ```ignore
+ * // `zcfg_flag_parser::FlagParser::collect_from_args` yields the flag candidates.
+ * let report = ConfigLoader::new()
+ *   .source(FileSource::new("/etc/myapp/prod.toml"))
+ *   .source(EnvParser::new("MYAPP"))
+ *   .source(flag_candidates)
+ *   .load()
+ *   .unwrap();
+ * ```
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use invalid_defaults;
+use InitErr;
+use ValueSource;
+use STATIC_CONFIG_INITIALIZERS;
+
+/** A value for a config, found by a `ConfigSource` but not yet applied. */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Candidate {
+  config_name: String,
+  value: String,
+  source: ValueSource,
+}
+
+impl Candidate {
+  pub fn new(config_name: String, value: String, source: ValueSource) -> Candidate {
+    Candidate {
+      config_name,
+      value,
+      source,
+    }
+  }
+
+  pub fn config_name(&self) -> &str {
+    &self.config_name
+  }
+
+  /** The unparsed value. */
+  pub fn value(&self) -> &str {
+    &self.value
+  }
+
+  pub fn source(&self) -> &ValueSource {
+    &self.source
+  }
+}
+
+/** Somewhere that config values can be read from. */
+pub trait ConfigSource {
+  /**
+   * Finds a value for each config that this source sets, without applying them.
+   *
+   * If a source yields several candidates for one config, the last one takes precedence.
+   */
+  fn candidates(&self) -> Result<Vec<Candidate>, Vec<Box<dyn Error>>>;
+}
+
+/** Candidates that were collected ahead of time, such as from command line flags. */
+impl ConfigSource for Vec<Candidate> {
+  fn candidates(&self) -> Result<Vec<Candidate>, Vec<Box<dyn Error>>> {
+    Ok(self.clone())
+  }
+}
+
+/** A candidate that lost to a higher precedence value for the same config. */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Shadowed {
+  candidate: Candidate,
+  shadowed_by: ValueSource,
+}
+
+impl Shadowed {
+  pub fn candidate(&self) -> &Candidate {
+    &self.candidate
+  }
+
+  /** The source of the value that was applied instead. */
+  pub fn shadowed_by(&self) -> &ValueSource {
+    &self.shadowed_by
+  }
+}
+
+impl fmt::Display for Shadowed {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}={} from {} was shadowed by {}",
+           self.candidate.config_name, self.candidate.value, self.candidate.source, self.shadowed_by)
+  }
+}
+
+/** What a successful `ConfigLoader::load` did besides applying values. */
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct LoadReport {
+  shadowed: Vec<Shadowed>,
+}
+
+impl LoadReport {
+  /** Every candidate that was not applied because a higher precedence one was. */
+  pub fn shadowed(&self) -> &[Shadowed] {
+    &self.shadowed
+  }
+}
+
+#[derive(Debug)]
+pub enum LoadErr {
+  /** A source could not produce its candidates. */
+  Source(Box<dyn Error>),
+  /** A candidate that matches no config. */
+  UndefinedArg(Candidate),
+  /** The winning candidate for a config could not be applied. */
+  InitErr(Candidate, InitErr),
+  /** A config's default value fails its validators. See `zcfg::invalid_defaults`. */
+  InvalidDefault(InitErr),
+}
+
+impl fmt::Display for LoadErr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      LoadErr::Source(ref e) => e.fmt(f),
+      LoadErr::UndefinedArg(ref candidate) => {
+        write!(f, "{}: unknown config `{}`", candidate.source, candidate.config_name)
+      },
+      LoadErr::InitErr(ref candidate, ref e) => {
+        write!(f, "{}: {}", candidate.source, e)
+      },
+      LoadErr::InvalidDefault(ref e) => write!(f, "invalid default: {}", e),
+    }
+  }
+}
+
+impl Error for LoadErr {}
+
+/**
+ * Applies each candidate to the config it names, and returns the error for each one that fails.
+ *
+ * Each candidate is paired with a tag, such as the position of the flag it came from, which is
+ * returned with its error so that parsers can say where the value was written. Every candidate
+ * must name a linked config.
+ *
+ * If any config's default is invalid, nothing is applied, and `invalid_defaults` is returned
+ * instead.
+ */
+pub fn apply_candidates<T, I>(candidates: I) -> Result<Vec<(T, InitErr)>, Vec<InitErr>>
+    where I: IntoIterator<Item = (Candidate, T)> {
+  let invalid = invalid_defaults();
+  if !invalid.is_empty() {
+    return Err(invalid)
+  }
+
+  let initializers = STATIC_CONFIG_INITIALIZERS.read()
+    .expect("initializers were poisoned");
+  let mut config_name_to_idx = HashMap::new();
+  for (idx, e) in initializers.iter().enumerate() {
+    config_name_to_idx.insert(e.config_name(), idx);
+  }
+
+  let mut errs = Vec::new();
+  for (candidate, tag) in candidates.into_iter() {
+    let initializer = config_name_to_idx.get(candidate.config_name.as_str())
+      .map(|idx| &initializers[*idx])
+      .expect("candidates only name known configs");
+    if let Err(e) = initializer.set_statically_from(&candidate.value, candidate.source.clone()) {
+      errs.push((tag, e))
+    }
+  }
+  Ok(errs)
+}
+
+/**
+ * Applies config values from several sources, each config at most once.
+ *
+ * Sources are consulted in the order they were added, and each takes precedence over all of the
+ * sources before it.
+ */
+#[derive(Default)]
+pub struct ConfigLoader {
+  sources: Vec<Box<dyn ConfigSource>>,
+}
+
+impl ConfigLoader {
+  pub fn new() -> ConfigLoader {
+    ConfigLoader::default()
+  }
+
+  /** Adds a source that takes precedence over every source added before it. */
+  pub fn source<S: ConfigSource + 'static>(mut self, source: S) -> ConfigLoader {
+    self.sources.push(Box::new(source));
+    self
+  }
+
+  /**
+   * Collects candidates from every source and applies the highest precedence one for each config.
+   *
+   * Nothing is applied if any source fails, or if any config's default is invalid. Otherwise every
+   * applicable value is applied, even if others fail to parse or match no config.
+   */
+  pub fn load(&self) -> Result<LoadReport, Vec<LoadErr>> {
+    let mut candidates = Vec::new();
+    let mut errs = Vec::new();
+    for source in self.sources.iter() {
+      match source.candidates() {
+        Ok(source_candidates) => candidates.extend(source_candidates),
+        Err(source_errs) => errs.extend(source_errs.into_iter().map(LoadErr::Source)),
+      }
+    }
+    if !errs.is_empty() {
+      return Err(errs)
+    }
+
+    let mut winner_idxs = HashMap::new();
+    for (idx, candidate) in candidates.iter().enumerate() {
+      winner_idxs.insert(candidate.config_name.clone(), idx);
+    }
+
+    let config_names = STATIC_CONFIG_INITIALIZERS.read()
+      .expect("initializers were poisoned")
+      .iter()
+      .map(|e| e.config_name().to_owned())
+      .collect::<HashSet<String>>();
+
+    let mut report = LoadReport::default();
+    let mut winners = Vec::new();
+    for (idx, candidate) in candidates.iter().enumerate() {
+      let winner_idx = winner_idxs[&candidate.config_name];
+      if idx != winner_idx {
+        report.shadowed.push(Shadowed {
+          candidate: candidate.clone(),
+          shadowed_by: candidates[winner_idx].source.clone(),
+        });
+        continue
+      }
+
+      if config_names.contains(&candidate.config_name) {
+        winners.push((candidate.clone(), candidate.clone()))
+      } else {
+        errs.push(LoadErr::UndefinedArg(candidate.clone()))
+      }
+    }
+
+    match apply_candidates(winners) {
+      Ok(apply_errs) => {
+        errs.extend(apply_errs.into_iter().map(|(candidate, e)| LoadErr::InitErr(candidate, e)))
+      },
+      Err(invalid_defaults) => errs.extend(invalid_defaults.into_iter().map(LoadErr::InvalidDefault)),
+    }
+
+    if errs.is_empty() {
+      Ok(report)
+    } else {
+      Err(errs)
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::path::PathBuf;
+  use ParseErr;
+
+  define_cfg!(loader_1, u32, 0u32, "some loader_1 configuration");
+  define_cfg!(loader_2, u32, 0u32, "some loader_2 configuration");
+  define_cfg!(loader_3, u32, 0u32, "some loader_3 configuration");
+  define_cfg!(loader_4, u32, 0u32, "some loader_4 configuration");
+  define_cfg!(loader_6, u32, 0u32, "some loader_6 configuration");
+  define_cfg!(loader_7, u32, 0u32, "some loader_7 configuration");
+
+  struct FailingSource;
+
+  impl ConfigSource for FailingSource {
+    fn candidates(&self) -> Result<Vec<Candidate>, Vec<Box<dyn Error>>> {
+      Err(vec![Box::new(ParseErr::new("", "bad source"))])
+    }
+  }
+
+  fn candidate(name: &str, value: &str, source: ValueSource) -> Candidate {
+    Candidate::new(name.to_owned(), value.to_owned(), source)
+  }
+
+  #[test]
+  fn later_sources_take_precedence() {
+    let file = ValueSource::File(PathBuf::from("app.toml"), 1);
+    let env = ValueSource::Environment("APP_LOADER_1".to_owned());
+
+    let report = ConfigLoader::new()
+      .source(vec![candidate("loader_1", "1", file.clone()), candidate("loader_2", "2", file.clone())])
+      .source(vec![candidate("loader_1", "10", env.clone())])
+      .source(vec![candidate("loader_1", "100", ValueSource::CommandLine)])
+      .load()
+      .unwrap();
+
+    assert_eq!(loader_1::CONFIG.get_value(), 100);
+    assert_eq!(loader_1::CONFIG.source(), ValueSource::CommandLine);
+    assert_eq!(loader_2::CONFIG.get_value(), 2);
+    assert_eq!(report.shadowed().iter().map(|s| s.to_string()).collect::<Vec<String>>(), vec![
+      "loader_1=1 from app.toml:1 was shadowed by command line flag".to_owned(),
+      "loader_1=10 from environment variable APP_LOADER_1 was shadowed by command line flag".to_owned(),
+    ]);
+  }
+
+  #[test]
+  fn shadowed_bad_values_are_never_parsed() {
+    let result = ConfigLoader::new()
+      .source(vec![candidate("loader_3", "x", ValueSource::Runtime)])
+      .source(vec![candidate("loader_3", "3", ValueSource::CommandLine)])
+      .load();
+
+    assert_eq!(result.map(|report| report.shadowed().len()).ok(), Some(1));
+    assert_eq!(loader_3::CONFIG.get_value(), 3);
+  }
+
+  #[test]
+  fn errors_are_reported_per_candidate() {
+    let errs = ConfigLoader::new()
+      .source(vec![candidate("loader_5", "1", ValueSource::CommandLine),
+                   candidate("loader_4", "x", ValueSource::CommandLine)])
+      .load()
+      .unwrap_err();
+
+    assert_eq!(errs.iter().map(|e| e.to_string()).collect::<Vec<String>>(), vec![
+      "command line flag: unknown config `loader_5`".to_owned(),
+      "command line flag: failed to parse value for `loader_4`: invalid digit found in string, expected u32".to_owned(),
+    ]);
+  }
+
+  #[test]
+  fn applied_candidates_report_errors_with_their_tags() {
+    let errs = apply_candidates(vec![
+      (candidate("loader_6", "x", ValueSource::CommandLine), "first"),
+      (candidate("loader_7", "7", ValueSource::CommandLine), "second"),
+    ]);
+
+    assert_eq!(errs.map(|errs| errs.into_iter().map(|(tag, e)| (tag, e.to_string())).collect::<Vec<_>>()),
+               Ok(vec![("first", "failed to parse value for `loader_6`: invalid digit found in string, expected u32"
+                 .to_owned())]));
+    assert_eq!(loader_6::CONFIG.source(), ValueSource::Default);
+    assert_eq!(loader_7::CONFIG.get_value(), 7);
+    assert_eq!(loader_7::CONFIG.source(), ValueSource::CommandLine);
+  }
+
+  #[test]
+  fn failing_sources_apply_nothing() {
+    let errs = ConfigLoader::new()
+      .source(vec![candidate("loader_4", "4", ValueSource::CommandLine)])
+      .source(FailingSource)
+      .load()
+      .unwrap_err();
+
+    assert_eq!(errs.iter().map(|e| e.to_string()).collect::<Vec<String>>(), vec![
+      "bad source".to_owned(),
+    ]);
+    assert_eq!(loader_4::CONFIG.source(), ValueSource::Default);
+  }
+}
@@ -0,0 +1,213 @@
+/*!
+ * Thread-local config values for tests.
+ *
+ * `Config::overlay_for_test` sets a value that only the current thread sees, so that tests running
+ * in parallel can each give the same config a different value. Reads check the current thread's
+ * overlays before the global value, and skip the check entirely while no thread has any.
+ *
+ * Threads start without overlays, including those spawned by a test. Carry them over with
+ * `spawn_with_overlays`, or with `Overlays::capture` and `Overlays::run` for scoped threads and
+ * thread pools.
+ */
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use loader::{Candidate, LoadErr};
+use STATIC_CONFIG_INITIALIZERS;
+
+/** An overlaid value, which is an `Arc<T>` for a config of type `T`. */
+pub(crate) type Value = Arc<dyn Any + Send + Sync>;
+
+/** How many overlays are set across all threads. */
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+  static OVERLAYS: RefCell<Layers> = RefCell::new(Layers::default());
+}
+
+#[derive(Default)]
+struct Layers {
+  next_id: usize,
+  // Keyed by the address of the config's storage. The last layer for a config is the one read.
+  by_config: HashMap<usize, Vec<(usize, Value)>>,
+}
+
+impl Drop for Layers {
+  fn drop(&mut self) {
+    let remaining = self.by_config.values().map(|layers| layers.len()).sum();
+    ACTIVE.fetch_sub(remaining, Ordering::Relaxed);
+  }
+}
+
+/** The value overlaid on the current thread for the config stored at `key`, if any. */
+pub(crate) fn lookup(key: usize) -> Option<Value> {
+  if ACTIVE.load(Ordering::Relaxed) == 0 {
+    return None
+  }
+
+  OVERLAYS.try_with(|layers| {
+    layers.borrow().by_config.get(&key).and_then(|stack| stack.last()).map(|layer| layer.1.clone())
+  }).ok().and_then(|value| value)
+}
+
+/** Removes an overlay when dropped. See `Config::overlay_for_test`. */
+#[must_use = "the overlay is removed as soon as the guard is dropped"]
+pub struct OverlayGuard {
+  key: usize,
+  id: usize,
+  // Overlays belong to the thread that set them, so the guard must be dropped there too.
+  _not_send: PhantomData<*const ()>,
+}
+
+impl OverlayGuard {
+  pub(crate) fn push(key: usize, value: Value) -> OverlayGuard {
+    let id = OVERLAYS.with(|layers| {
+      let mut layers = layers.borrow_mut();
+      let id = layers.next_id;
+      layers.next_id += 1;
+      layers.by_config.entry(key).or_default().push((id, value));
+      id
+    });
+    ACTIVE.fetch_add(1, Ordering::Relaxed);
+
+    OverlayGuard {
+      key,
+      id,
+      _not_send: PhantomData,
+    }
+  }
+}
+
+impl Drop for OverlayGuard {
+  fn drop(&mut self) {
+    // The layers are already gone if the guard outlives its thread's locals.
+    let _ = OVERLAYS.try_with(|layers| {
+      let mut layers = layers.borrow_mut();
+      let now_empty = match layers.by_config.get_mut(&self.key) {
+        Some(stack) => {
+          if let Some(idx) = stack.iter().position(|layer| layer.0 == self.id) {
+            stack.remove(idx);
+            ACTIVE.fetch_sub(1, Ordering::Relaxed);
+          }
+          stack.is_empty()
+        },
+        None => false,
+      };
+      if now_empty {
+        layers.by_config.remove(&self.key);
+      }
+    });
+  }
+}
+
+/** A copy of one thread's overlays, which can be applied on another. */
+#[derive(Clone)]
+pub struct Overlays {
+  values: Vec<(usize, Value)>,
+}
+
+impl Overlays {
+  /** Copies the overlays currently visible on this thread. */
+  pub fn capture() -> Overlays {
+    let values = OVERLAYS.with(|layers| {
+      layers.borrow().by_config.iter()
+        .filter_map(|(key, stack)| stack.last().map(|layer| (*key, layer.1.clone())))
+        .collect()
+    });
+    Overlays { values }
+  }
+
+  /** Runs `f` with these overlays applied on the current thread, on top of any it already has. */
+  pub fn run<R, F: FnOnce() -> R>(&self, f: F) -> R {
+    let _guards = self.values.iter()
+      .map(|(key, value)| OverlayGuard::push(*key, value.clone()))
+      .collect::<Vec<OverlayGuard>>();
+    f()
+  }
+}
+
+/**
+ * Overlays the config named by the candidate with the candidate's value, on the current thread.
+ *
+ * This is how `#[zcfg::test]` sets configs that it names without a path.
+ */
+// Errors are as large as `ConfigLoader`'s, which is fine for a function called once per test.
+#[allow(clippy::result_large_err)]
+pub fn overlay_candidate(candidate: Candidate) -> Result<OverlayGuard, LoadErr> {
+  let initializers = STATIC_CONFIG_INITIALIZERS.read().expect("initializers were poisoned");
+  let initializer = match initializers.iter().find(|i| i.config_name() == candidate.config_name()) {
+    Some(initializer) => initializer,
+    None => return Err(LoadErr::UndefinedArg(candidate)),
+  };
+  initializer.overlay_for_test(candidate.value()).map_err(|e| LoadErr::InitErr(candidate, e))
+}
+
+/** Like `std::thread::spawn`, but the new thread sees the current thread's overlays. */
+pub fn spawn_with_overlays<F, R>(f: F) -> thread::JoinHandle<R>
+    where F: FnOnce() -> R + Send + 'static, R: Send + 'static {
+  let overlays = Overlays::capture();
+  thread::spawn(move || overlays.run(f))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use ValueSource;
+
+  // These tests don't take the crate's test lock: overlays are invisible to other tests.
+  define_cfg!(overlay_1, String, "global".to_owned(), "some overlay_1 configuration");
+  define_cfg!(overlay_2, u32, 1u32, "some overlay_2 configuration");
+  define_cfg!(overlay_3, u32, 1u32, "some overlay_3 configuration");
+  define_cfg!(overlay_4, String, "global".to_owned(), "some overlay_4 configuration");
+
+  #[test]
+  fn overlays_are_only_seen_by_their_thread() {
+    let _overlay = overlay_1::CONFIG.overlay_for_test("local".to_owned());
+    assert_eq!(overlay_1::CONFIG.get_value(), "local".to_owned());
+    assert_eq!(overlay_1::CONFIG.source(), ValueSource::Testing);
+
+    let elsewhere = thread::spawn(|| overlay_1::CONFIG.get_value()).join().unwrap();
+    assert_eq!(elsewhere, "global".to_owned());
+    let propagated = spawn_with_overlays(|| overlay_1::CONFIG.get_value()).join().unwrap();
+    assert_eq!(propagated, "local".to_owned());
+  }
+
+  #[test]
+  fn overlays_nest_and_fall_back_to_the_global_value() {
+    {
+      let _outer = overlay_2::CONFIG.overlay_for_test(2);
+      let inner = overlay_2::CONFIG.overlay_for_test(3);
+      assert_eq!((overlay_2::CONFIG.get_value(), *overlay_2::CONFIG.get_arc()), (3, 3));
+
+      let overlays = Overlays::capture();
+      thread::scope(|scope| {
+        scope.spawn(|| assert_eq!(overlays.run(|| overlay_2::CONFIG.get_value()), 3));
+      });
+      drop(inner);
+      assert_eq!(overlay_2::CONFIG.with_value(|v| *v), 2);
+    }
+    assert_eq!(overlay_2::CONFIG.get_value(), 1);
+    assert_eq!(overlay_2::CONFIG.source(), ValueSource::Default);
+  }
+
+  #[test]
+  fn overrides_inside_overlays_restore_the_global_value() {
+    {
+      let _overlay = overlay_3::CONFIG.overlay_for_test(99);
+      let _restore = overlay_3::CONFIG.override_for_test(5);
+      assert_eq!(overlay_3::CONFIG.get_value(), 99);
+    }
+    {
+      let _overlay = overlay_4::CONFIG.overlay_for_test("local".to_owned());
+      let _restore = overlay_4::CONFIG.override_for_test("override".to_owned());
+    }
+    let global = thread::spawn(|| (overlay_3::CONFIG.get_value(), overlay_4::CONFIG.get_value()));
+    assert_eq!(global.join().unwrap(), (1, "global".to_owned()));
+    assert_eq!(overlay_3::CONFIG.source(), ValueSource::Default);
+  }
+}
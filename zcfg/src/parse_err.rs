@@ -0,0 +1,151 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/**
+ * Why a string could not be parsed by `ConfigParseable::parse_from_str`.
+ *
+ * The error points at the bytes of the input that are at fault, so that a caller can underline
+ * them (see `underline`). Errors inside a collection or derived type wrap the error for the
+ * offending element as their `cause`, whose input and span are relative to that element.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseErr {
+  // Boxed so that results carrying the error stay small.
+  inner: Box<Inner>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Inner {
+  input: String,
+  span: Range<usize>,
+  message: String,
+  expected: Option<String>,
+  cause: Option<ParseErr>,
+}
+
+impl ParseErr {
+  /** Constructs an error about all of `input`. */
+  pub fn new<M: Into<String>>(input: &str, message: M) -> ParseErr {
+    ParseErr {
+      inner: Box::new(Inner {
+        input: input.to_owned(),
+        span: 0..input.len(),
+        message: message.into(),
+        expected: None,
+        cause: None,
+      }),
+    }
+  }
+
+  /**
+   * Narrows the error to a byte range within the input.
+   *
+   * The range is clamped to the input, and widened to the nearest character boundaries.
+   */
+  pub fn at(mut self, span: Range<usize>) -> ParseErr {
+    let mut start = span.start.min(self.inner.input.len());
+    let mut end = span.end.max(start).min(self.inner.input.len());
+    while !self.inner.input.is_char_boundary(start) {
+      start -= 1;
+    }
+    while !self.inner.input.is_char_boundary(end) {
+      end += 1;
+    }
+    self.inner.span = start..end;
+    self
+  }
+
+  /** Describes the format that was expected, like "a number and a unit, like `1500ms`". */
+  pub fn expecting<E: Into<String>>(mut self, expected: E) -> ParseErr {
+    self.inner.expected = Some(expected.into());
+    self
+  }
+
+  /** Attaches the error for the part of the input at `span`, such as one element of a list. */
+  pub fn caused_by(mut self, cause: ParseErr) -> ParseErr {
+    self.inner.cause = Some(cause);
+    self
+  }
+
+  /** The text that failed to parse. */
+  pub fn input(&self) -> &str {
+    &self.inner.input
+  }
+
+  /** The byte range of `input` that is at fault. */
+  pub fn span(&self) -> Range<usize> {
+    self.inner.span.clone()
+  }
+
+  /** What went wrong, without the expected format or cause. */
+  pub fn message(&self) -> &str {
+    &self.inner.message
+  }
+
+  /** The format that was expected, if the parser described it. */
+  pub fn expected(&self) -> Option<&str> {
+    self.inner.expected.as_deref()
+  }
+
+  /** The error for the part of the input at `span`, for errors inside collections and options. */
+  pub fn cause(&self) -> Option<&ParseErr> {
+    self.inner.cause.as_ref()
+  }
+
+  /**
+   * Renders the input with the span underlined, as in:
+   *
+   * ```text
+   * 5 sec
+   *   ^^^
+   * ```
+   *
+   * An empty span, such as a missing character at the end of the input, is shown as one caret.
+   */
+  pub fn underline(&self) -> String {
+    let offset = self.inner.input[..self.inner.span.start].chars().count();
+    let width = self.inner.input[self.inner.span.clone()].chars().count().max(1);
+    format!("{}\n{}{}", self.inner.input, " ".repeat(offset), "^".repeat(width))
+  }
+}
+
+impl fmt::Display for ParseErr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.inner.message)?;
+    if let Some(ref expected) = self.inner.expected {
+      write!(f, ", expected {}", expected)?;
+    }
+    if let Some(ref cause) = self.inner.cause {
+      write!(f, ": {}", cause)?;
+    }
+    Ok(())
+  }
+}
+
+// The cause is already part of the message, so it is not reported again as the `source`.
+impl Error for ParseErr {}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn errors_display_their_expectation_and_cause() {
+    let inner = ParseErr::new("x", "invalid digit found in string").expecting("u32");
+    let err = ParseErr::new("1,x", "list element 1").at(2..3).caused_by(inner.clone());
+
+    assert_eq!(err.to_string(), "list element 1: invalid digit found in string, expected u32");
+    assert_eq!(err.cause(), Some(&inner));
+    assert!(err.source().is_none());
+    assert_eq!(err.span(), 2..3);
+  }
+
+  #[test]
+  fn spans_are_underlined() {
+    assert_eq!(ParseErr::new("5 sec", "unknown unit").at(2..5).underline(), "5 sec\n  ^^^".to_owned());
+    assert_eq!(ParseErr::new("µs!", "x").at(2..3).underline(), "µs!\n ^".to_owned());
+    assert_eq!(ParseErr::new("(a", "expected `)`").at(2..2).underline(), "(a\n  ^".to_owned());
+    assert_eq!(ParseErr::new("ab", "x").at(5..9).span(), 2..2);
+  }
+}
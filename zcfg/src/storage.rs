@@ -0,0 +1,386 @@
+/*!
+ * The storage behind each config's value.
+ *
+ * Reads are wait-free: primitive configs are held in an atomic integer, and everything else in an
+ * atomically swapped `Arc<T>`. Writes, which only happen during initialization and in tests, are
+ * serialized by a mutex that also guards the config's initialized state and source.
+ *
+ * `define_cfg!` picks the representation with `Probe`: the atomic one is implemented for
+ * `&Probe<T>` when `T: AtomicPrimitive`, and the shared one for `&&Probe<T>` and any `T`. Method
+ * resolution tries the fewest references first, so `(&&Probe::<T>::new()).__new_value(v)` is atomic
+ * whenever it can be.
+ *
+ * Targets without 64-bit atomics store every config in an `ArcSwap`.
+ */
+
+use arc_swap::ArcSwap;
+use std::marker::PhantomData;
+#[cfg(target_has_atomic = "64")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+use overlay::{self, OverlayGuard};
+use ValueSource;
+
+/** A type that round-trips through a `u64`, so that it can be stored in an atomic. */
+pub trait AtomicPrimitive: Copy {
+  fn to_bits(self) -> u64;
+  fn from_bits(bits: u64) -> Self;
+}
+
+macro_rules! atomic_primitive_as {
+  ($($primitive:ty),*) => {
+    $(
+      #[cfg(target_has_atomic = "64")]
+      impl AtomicPrimitive for $primitive {
+        fn to_bits(self) -> u64 {
+          self as u64
+        }
+
+        fn from_bits(bits: u64) -> $primitive {
+          bits as $primitive
+        }
+      }
+    )*
+  };
+}
+
+atomic_primitive_as!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+#[cfg(target_has_atomic = "64")]
+impl AtomicPrimitive for bool {
+  fn to_bits(self) -> u64 {
+    self as u64
+  }
+
+  fn from_bits(bits: u64) -> bool {
+    bits != 0
+  }
+}
+
+#[cfg(target_has_atomic = "64")]
+impl AtomicPrimitive for char {
+  fn to_bits(self) -> u64 {
+    self as u64
+  }
+
+  fn from_bits(bits: u64) -> char {
+    ::std::char::from_u32(bits as u32).expect("only chars are stored")
+  }
+}
+
+#[cfg(target_has_atomic = "64")]
+impl AtomicPrimitive for f32 {
+  fn to_bits(self) -> u64 {
+    f32::to_bits(self) as u64
+  }
+
+  fn from_bits(bits: u64) -> f32 {
+    f32::from_bits(bits as u32)
+  }
+}
+
+#[cfg(target_has_atomic = "64")]
+impl AtomicPrimitive for f64 {
+  fn to_bits(self) -> u64 {
+    f64::to_bits(self)
+  }
+
+  fn from_bits(bits: u64) -> f64 {
+    f64::from_bits(bits)
+  }
+}
+
+enum Repr<T> {
+  #[cfg(target_has_atomic = "64")]
+  Atomic {
+    bits: AtomicU64,
+    to_bits: fn(&T) -> u64,
+    from_bits: fn(u64) -> T,
+  },
+  Shared(ArcSwap<T>),
+}
+
+struct WriteState<T> {
+  initialized: bool,
+  source: ValueSource,
+  next_override_id: usize,
+  overrides: Vec<Override<T>>,
+}
+
+/** The state that an override replaced, to be restored when it ends. */
+struct Override<T> {
+  id: usize,
+  value: T,
+  initialized: bool,
+  source: ValueSource,
+}
+
+/** The inner config value, public to allow access by `define_cfg` macros. */
+pub struct __ConfigValue<T: Clone> {
+  repr: Repr<T>,
+  write_state: Mutex<WriteState<T>>,
+}
+
+impl<T: Clone + 'static> __ConfigValue<T> {
+  /** Stores the value in an atomically swapped `Arc`, which works for any type. */
+  pub fn new(default: T) -> __ConfigValue<T> {
+    __ConfigValue::with_repr(Repr::Shared(ArcSwap::from_pointee(default)))
+  }
+
+  fn with_repr(repr: Repr<T>) -> __ConfigValue<T> {
+    __ConfigValue {
+      repr,
+      write_state: Mutex::new(WriteState {
+        initialized: false,
+        source: ValueSource::Default,
+        next_override_id: 0,
+        overrides: Vec::new(),
+      }),
+    }
+  }
+
+  pub fn get(&self) -> T {
+    match self.overlay() {
+      Some(overlay) => T::clone(&overlay),
+      None => self.load_global(),
+    }
+  }
+
+  /** The value seen by every thread, ignoring this thread's overlays. */
+  fn load_global(&self) -> T {
+    match self.repr {
+      #[cfg(target_has_atomic = "64")]
+      Repr::Atomic { ref bits, from_bits, .. } => from_bits(bits.load(Ordering::Acquire)),
+      Repr::Shared(ref shared) => T::clone(&shared.load()),
+    }
+  }
+
+  /** Calls `f` with the current value, without cloning it unless it is a primitive. */
+  pub fn with<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
+    if let Some(overlay) = self.overlay() {
+      return f(&overlay)
+    }
+    match self.repr {
+      #[cfg(target_has_atomic = "64")]
+      Repr::Atomic { ref bits, from_bits, .. } => f(&from_bits(bits.load(Ordering::Acquire))),
+      Repr::Shared(ref shared) => f(&shared.load()),
+    }
+  }
+
+  /** A snapshot of the current value, which is only allocated for primitives. */
+  pub fn get_arc(&self) -> Arc<T> {
+    if let Some(overlay) = self.overlay() {
+      return overlay
+    }
+    match self.repr {
+      #[cfg(target_has_atomic = "64")]
+      Repr::Atomic { ref bits, from_bits, .. } => Arc::new(from_bits(bits.load(Ordering::Acquire))),
+      Repr::Shared(ref shared) => shared.load_full(),
+    }
+  }
+
+  pub fn source(&self) -> ValueSource {
+    if self.overlay().is_some() {
+      return ValueSource::Testing
+    }
+    self.write_state().source.clone()
+  }
+
+  /** Sets the value seen by the current thread until the guard is dropped. See `overlay`. */
+  pub fn begin_overlay(&self, t: T) -> OverlayGuard where T: Send + Sync {
+    OverlayGuard::push(self.key(), Arc::new(Arc::new(t)))
+  }
+
+  fn overlay(&self) -> Option<Arc<T>> {
+    overlay::lookup(self.key()).map(|value| {
+      value.downcast_ref::<Arc<T>>().expect("overlays hold values of their config's type").clone()
+    })
+  }
+
+  /** Identifies the config in the overlays, which works because the value is always a static. */
+  fn key(&self) -> usize {
+    self as *const __ConfigValue<T> as usize
+  }
+
+  pub fn set_raw(&self, t: T, source: ValueSource) {
+    let mut write_state = self.write_state();
+    self.store(t);
+    write_state.source = source;
+  }
+
+  pub fn initialize(&self, t: T, source: ValueSource) -> bool {
+    let mut write_state = self.write_state();
+    if write_state.initialized {
+      return false
+    }
+
+    self.store(t);
+    write_state.source = source;
+    write_state.initialized = true;
+    true
+  }
+
+  /**
+   * Sets the value for testing until `end_override` is called with the returned id, which restores
+   * the value, source and initialized state from before.
+   */
+  pub fn begin_override(&self, t: T) -> usize {
+    let mut write_state = self.write_state();
+    let id = write_state.next_override_id;
+    write_state.next_override_id += 1;
+    let saved = Override {
+      id,
+      value: self.load_global(),
+      initialized: write_state.initialized,
+      source: write_state.source.clone(),
+    };
+    write_state.overrides.push(saved);
+    self.store(t);
+    write_state.source = ValueSource::Testing;
+    id
+  }
+
+  /**
+   * Ends the override with the given id.
+   *
+   * Overrides normally end in the reverse order that they began. If a later override is still
+   * active, it takes over the state that this one saved, so that it is restored once the later
+   * override ends.
+   */
+  pub fn end_override(&self, id: usize) {
+    let mut write_state = self.write_state();
+    let idx = match write_state.overrides.iter().position(|o| o.id == id) {
+      Some(idx) => idx,
+      None => return,
+    };
+    let ended = write_state.overrides.remove(idx);
+    if idx < write_state.overrides.len() {
+      write_state.overrides[idx].value = ended.value;
+      write_state.overrides[idx].initialized = ended.initialized;
+      write_state.overrides[idx].source = ended.source;
+      return
+    }
+
+    self.store(ended.value);
+    write_state.initialized = ended.initialized;
+    write_state.source = ended.source;
+  }
+
+  fn store(&self, t: T) {
+    match self.repr {
+      #[cfg(target_has_atomic = "64")]
+      Repr::Atomic { ref bits, to_bits, .. } => bits.store(to_bits(&t), Ordering::Release),
+      Repr::Shared(ref shared) => shared.store(Arc::new(t)),
+    }
+  }
+
+  fn write_state(&self) -> ::std::sync::MutexGuard<'_, WriteState<T>> {
+    // A panic while holding the lock can't leave the state half-written, so poison is ignored.
+    self.write_state.lock().unwrap_or_else(|e| e.into_inner())
+  }
+}
+
+#[cfg(target_has_atomic = "64")]
+impl<T: AtomicPrimitive + 'static> __ConfigValue<T> {
+  /** Stores the value in an atomic integer. */
+  pub fn atomic(default: T) -> __ConfigValue<T> {
+    __ConfigValue::with_repr(Repr::Atomic {
+      bits: AtomicU64::new(default.to_bits()),
+      to_bits: |t| t.to_bits(),
+      from_bits: T::from_bits,
+    })
+  }
+}
+
+/** Selects the representation for a `T`. See the module documentation. */
+pub struct Probe<T>(PhantomData<T>);
+
+impl<T> Probe<T> {
+  #[allow(clippy::new_without_default)]
+  pub fn new() -> Probe<T> {
+    Probe(PhantomData)
+  }
+}
+
+pub trait AtomicStorage<T: Clone> {
+  fn __new_value(&self, default: T) -> __ConfigValue<T>;
+}
+
+#[cfg(target_has_atomic = "64")]
+impl<T: AtomicPrimitive + 'static> AtomicStorage<T> for &Probe<T> {
+  fn __new_value(&self, default: T) -> __ConfigValue<T> {
+    __ConfigValue::atomic(default)
+  }
+}
+
+pub trait SharedStorage<T: Clone> {
+  fn __new_value(&self, default: T) -> __ConfigValue<T>;
+}
+
+impl<T: Clone + 'static> SharedStorage<T> for &&Probe<T> {
+  fn __new_value(&self, default: T) -> __ConfigValue<T> {
+    __ConfigValue::new(default)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[cfg(target_has_atomic = "64")]
+  fn is_atomic<T: Clone>(value: &__ConfigValue<T>) -> bool {
+    match value.repr {
+      Repr::Atomic { .. } => true,
+      Repr::Shared(_) => false,
+    }
+  }
+
+  #[test]
+  #[cfg(target_has_atomic = "64")]
+  fn primitives_are_stored_atomically() {
+    assert!(is_atomic(&(&&Probe::<u32>::new()).__new_value(5)));
+    assert!(is_atomic(&(&&Probe::<f64>::new()).__new_value(0.5)));
+    assert!(!is_atomic(&(&&Probe::<String>::new()).__new_value(String::new())));
+    assert!(!is_atomic(&(&&Probe::<Option<u32>>::new()).__new_value(None)));
+  }
+
+  #[test]
+  #[cfg(target_has_atomic = "64")]
+  fn primitives_round_trip_through_bits() {
+    assert_eq!(i8::from_bits((-5i8).to_bits()), -5);
+    assert_eq!(i64::from_bits(i64::MIN.to_bits()), i64::MIN);
+    assert_eq!(<f32 as AtomicPrimitive>::from_bits(AtomicPrimitive::to_bits(-1.5f32)), -1.5);
+    assert_eq!(char::from_bits('µ'.to_bits()), 'µ');
+    assert!(bool::from_bits(true.to_bits()));
+  }
+
+  fn check_initialized_once(value: __ConfigValue<u32>) {
+    assert_eq!(value.get(), 1);
+    assert!(value.initialize(2, ValueSource::CommandLine));
+    assert!(!value.initialize(3, ValueSource::CommandLine));
+    assert_eq!((value.get(), value.source()), (2, ValueSource::CommandLine));
+    value.set_raw(4, ValueSource::Testing);
+    assert_eq!((value.get(), value.source()), (4, ValueSource::Testing));
+  }
+
+  #[test]
+  fn values_are_initialized_once() {
+    #[cfg(target_has_atomic = "64")]
+    check_initialized_once(__ConfigValue::atomic(1u32));
+    check_initialized_once(__ConfigValue::new(1u32));
+  }
+
+  #[test]
+  fn reads_see_whole_values_while_writing() {
+    let value = __ConfigValue::new(vec![0u32; 16]);
+    ::std::thread::scope(|scope| {
+      scope.spawn(|| for i in 1..1000 { value.set_raw(vec![i; 16], ValueSource::Testing) });
+      for _ in 0..1000 {
+        let read = value.get();
+        assert!(read.iter().all(|v| *v == read[0]));
+      }
+    });
+    assert_eq!(value.get(), vec![999; 16]);
+  }
+}
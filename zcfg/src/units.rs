@@ -0,0 +1,438 @@
+/*!
+ * Config types for quantities that operators write with units.
+ */
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use ConfigParseable;
+use ParseErr;
+
+/** Byte unit prefixes, with their size, from largest to smallest within each family. */
+const BYTE_UNITS: &[(&str, u64)] = &[
+  ("Pi", 1 << 50),
+  ("Ti", 1 << 40),
+  ("Gi", 1 << 30),
+  ("Mi", 1 << 20),
+  ("Ki", 1 << 10),
+  ("P", 1_000_000_000_000_000),
+  ("T", 1_000_000_000_000),
+  ("G", 1_000_000_000),
+  ("M", 1_000_000),
+  ("k", 1_000),
+  ("", 1),
+];
+
+/** The unit a size was written in: a prefix from `BYTE_UNITS`, and whether a `B` followed it. */
+#[derive(Debug, Clone, Copy)]
+struct ByteUnit {
+  prefix: &'static str,
+  bytes: u64,
+  suffixed: bool,
+}
+
+/**
+ * A number of bytes, written with an optional SI or IEC suffix.
+ *
+ * `10k` and `10kB` are 10,000 bytes, while `4Ki` and `4KiB` are 4,096. Suffixes are
+ * case-insensitive, and a bare number is a count of bytes. Fractions like `1.5GB` are allowed as
+ * long as they come to a whole number of bytes.
+ *
+ * A parsed size remembers its unit, so that it is displayed the way the operator wrote it. Sizes
+ * compare by their number of bytes alone.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByteSize {
+  bytes: u64,
+  unit: Option<ByteUnit>,
+}
+
+impl ByteSize {
+  pub fn new(bytes: u64) -> ByteSize {
+    ByteSize { bytes, unit: None }
+  }
+
+  pub fn bytes(&self) -> u64 {
+    self.bytes
+  }
+}
+
+impl PartialEq for ByteSize {
+  fn eq(&self, other: &ByteSize) -> bool {
+    self.bytes == other.bytes
+  }
+}
+
+impl Eq for ByteSize {}
+
+impl PartialOrd for ByteSize {
+  fn partial_cmp(&self, other: &ByteSize) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for ByteSize {
+  fn cmp(&self, other: &ByteSize) -> Ordering {
+    self.bytes.cmp(&other.bytes)
+  }
+}
+
+impl Hash for ByteSize {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.bytes.hash(state)
+  }
+}
+
+/**
+ * Renders the size in the unit it was parsed with, like `1.5GB` or `10k`.
+ *
+ * Sizes that weren't parsed use the exact unit that gives the smallest number, like `512MiB`.
+ */
+impl fmt::Display for ByteSize {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let unit = self.unit.unwrap_or_else(|| {
+      let &(prefix, bytes) = BYTE_UNITS.iter()
+        .filter(|&&(_, unit_bytes)| self.bytes.is_multiple_of(unit_bytes))
+        .min_by_key(|&&(_, unit_bytes)| if self.bytes == 0 { unit_bytes } else { self.bytes / unit_bytes })
+        .expect("every size is a whole number of bytes");
+      ByteUnit { prefix, bytes, suffixed: true }
+    });
+
+    write!(f, "{}", self.bytes / unit.bytes)?;
+    // Every unit is a product of twos and fives, so the fraction always ends.
+    let mut remainder = self.bytes % unit.bytes;
+    if remainder != 0 {
+      write!(f, ".")?;
+    }
+    while remainder != 0 {
+      write!(f, "{}", remainder * 10 / unit.bytes)?;
+      remainder = remainder * 10 % unit.bytes;
+    }
+    write!(f, "{}{}", unit.prefix, if unit.suffixed { "B" } else { "" })
+  }
+}
+
+impl ConfigParseable for ByteSize {
+  type Output = ByteSize;
+  fn parse_from_str(s: &str) -> Result<ByteSize, ParseErr> {
+    let leading_space = s.len() - s.trim_start().len();
+    let trimmed = s.trim();
+    let number_len = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(number_len);
+    let suffix = suffix.trim_start();
+    let number_span = leading_space..leading_space + number_len;
+    let suffix_span = leading_space + trimmed.len() - suffix.len()..leading_space + trimmed.len();
+
+    let unit = byte_unit(suffix).ok_or_else(|| {
+      ParseErr::new(s, format!("unknown size suffix `{}`", suffix))
+        .at(suffix_span)
+        .expecting("one of: k, M, G, T, P, Ki, Mi, Gi, Ti, Pi, optionally followed by B")
+    })?;
+    let (whole, fraction) = split_decimal(number).ok_or_else(|| {
+      ParseErr::new(s, format!("invalid size `{}`", trimmed))
+        .at(number_span.clone())
+        .expecting("a number and a suffix, like `512MiB`")
+    })?;
+
+    // No unit has more than 19 digits after the point, so longer fractions can't be whole bytes.
+    let fraction = fraction.trim_end_matches('0');
+    let fraction_value = if fraction.is_empty() { 0 } else { fraction.parse::<u128>().unwrap_or(1) };
+    let scale = 10u128.pow(fraction.len().min(20) as u32);
+    let fraction_bytes = fraction_value * unit.bytes as u128;
+    if fraction.len() > 19 || !fraction_bytes.is_multiple_of(scale) {
+      return Err(ParseErr::new(s, format!("`{}` is not a whole number of bytes", trimmed)).at(number_span))
+    }
+
+    whole.checked_mul(unit.bytes as u128)
+      .and_then(|bytes| bytes.checked_add(fraction_bytes / scale))
+      .and_then(|bytes| if bytes <= u64::MAX as u128 { Some(bytes as u64) } else { None })
+      .map(|bytes| ByteSize { bytes, unit: Some(unit) })
+      .ok_or_else(|| ParseErr::new(s, format!("`{}` is too large", trimmed)))
+  }
+
+  fn display_value(value: &ByteSize) -> Option<String> {
+    Some(value.to_string())
+  }
+}
+
+/** The unit named by a byte suffix, such as `k`, `KB`, `Ki` or `KiB`, matched case-insensitively. */
+fn byte_unit(suffix: &str) -> Option<ByteUnit> {
+  let (prefix, suffixed) = match suffix.strip_suffix(&['B', 'b'][..]) {
+    Some(prefix) => (prefix, true),
+    None => (suffix, false),
+  };
+  BYTE_UNITS.iter()
+    .find(|&&(unit_prefix, _)| unit_prefix.eq_ignore_ascii_case(prefix))
+    .map(|&(prefix, bytes)| ByteUnit { prefix, bytes, suffixed })
+}
+
+/** Splits an unsigned decimal like `1.5` into its whole part and its fraction digits. */
+fn split_decimal(number: &str) -> Option<(u128, &str)> {
+  let (whole, fraction) = match number.find('.') {
+    Some(dot_idx) => (&number[..dot_idx], &number[dot_idx + 1..]),
+    None => (number, ""),
+  };
+  if (whole.is_empty() && fraction.is_empty()) || fraction.contains('.') {
+    return None
+  }
+  let whole = if whole.is_empty() { 0 } else { whole.parse::<u128>().ok()? };
+  Some((whole, fraction))
+}
+
+/** How a proportion was written: as a percentage like `75%`, or as a fraction like `0.75`. */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ProportionForm {
+  Percentage,
+  Fraction,
+}
+
+/** Renders `fraction` in the given form. */
+fn display_proportion(f: &mut fmt::Formatter, fraction: f64, form: ProportionForm) -> fmt::Result {
+  match form {
+    ProportionForm::Percentage => write!(f, "{}%", shift_decimal(&fraction.to_string(), 2)),
+    ProportionForm::Fraction => write!(f, "{}", fraction),
+  }
+}
+
+/**
+ * A proportion, written as a percentage like `75%` or as a fraction like `0.75`.
+ *
+ * The value is held as a fraction. A parsed proportion is displayed the way it was written, and
+ * any other is displayed as a percentage. Proportions compare by their value alone.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Percent {
+  fraction: f64,
+  form: Option<ProportionForm>,
+}
+
+impl Percent {
+  /** A proportion of `fraction`, so 0.75 for `75%`. */
+  pub fn new(fraction: f64) -> Percent {
+    Percent { fraction, form: None }
+  }
+
+  /** The proportion as a fraction, so 0.75 for `75%`. */
+  pub fn fraction(&self) -> f64 {
+    self.fraction
+  }
+}
+
+impl PartialEq for Percent {
+  fn eq(&self, other: &Percent) -> bool {
+    self.fraction == other.fraction
+  }
+}
+
+impl PartialOrd for Percent {
+  fn partial_cmp(&self, other: &Percent) -> Option<Ordering> {
+    self.fraction.partial_cmp(&other.fraction)
+  }
+}
+
+impl fmt::Display for Percent {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    display_proportion(f, self.fraction, self.form.unwrap_or(ProportionForm::Percentage))
+  }
+}
+
+impl ConfigParseable for Percent {
+  type Output = Percent;
+  fn parse_from_str(s: &str) -> Result<Percent, ParseErr> {
+    parse_proportion(s).map(|(fraction, form)| Percent { fraction, form: Some(form) })
+  }
+
+  fn display_value(value: &Percent) -> Option<String> {
+    Some(value.to_string())
+  }
+}
+
+/**
+ * A proportion, written as a fraction like `0.75` or as a percentage like `75%`.
+ *
+ * This is `Percent`, except that proportions which weren't parsed are displayed as fractions.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ratio {
+  fraction: f64,
+  form: Option<ProportionForm>,
+}
+
+impl Ratio {
+  pub fn new(fraction: f64) -> Ratio {
+    Ratio { fraction, form: None }
+  }
+
+  pub fn fraction(&self) -> f64 {
+    self.fraction
+  }
+}
+
+impl PartialEq for Ratio {
+  fn eq(&self, other: &Ratio) -> bool {
+    self.fraction == other.fraction
+  }
+}
+
+impl PartialOrd for Ratio {
+  fn partial_cmp(&self, other: &Ratio) -> Option<Ordering> {
+    self.fraction.partial_cmp(&other.fraction)
+  }
+}
+
+impl fmt::Display for Ratio {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    display_proportion(f, self.fraction, self.form.unwrap_or(ProportionForm::Fraction))
+  }
+}
+
+impl ConfigParseable for Ratio {
+  type Output = Ratio;
+  fn parse_from_str(s: &str) -> Result<Ratio, ParseErr> {
+    parse_proportion(s).map(|(fraction, form)| Ratio { fraction, form: Some(form) })
+  }
+
+  fn display_value(value: &Ratio) -> Option<String> {
+    Some(value.to_string())
+  }
+}
+
+/** Parses `75%` or `0.75` as the fraction 0.75, and reports which way it was written. */
+fn parse_proportion(s: &str) -> Result<(f64, ProportionForm), ParseErr> {
+  let trimmed = s.trim();
+  let invalid = || {
+    ParseErr::new(s, format!("invalid proportion `{}`", trimmed))
+      .expecting("a percentage like `75%` or a fraction like `0.75`")
+  };
+  let (number, places, form) = match trimmed.strip_suffix('%') {
+    Some(percent) => (percent.trim_end(), -2, ProportionForm::Percentage),
+    None => (trimmed, 0, ProportionForm::Fraction),
+  };
+  let (negative, digits) = match number.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, number),
+  };
+  if !digits.chars().any(|c| c.is_ascii_digit()) || !digits.chars().all(|c| c.is_ascii_digit() || c == '.') {
+    return Err(invalid())
+  }
+  if negative {
+    return Err(ParseErr::new(s, format!("proportion `{}` is negative", trimmed)))
+  }
+  // Shifting the digits, rather than dividing by 100, keeps `7%` exactly equal to `0.07`.
+  let fraction = shift_decimal(number, places).parse::<f64>().map_err(|_| invalid())?;
+  Ok((fraction, form))
+}
+
+/** Moves the decimal point of a plain decimal string `places` digits to the right. */
+fn shift_decimal(number: &str, places: i32) -> String {
+  let (sign, digits) = match number.strip_prefix('-') {
+    Some(digits) => ("-", digits),
+    None => ("", number),
+  };
+  let (whole, fraction) = match digits.find('.') {
+    Some(dot_idx) => (&digits[..dot_idx], &digits[dot_idx + 1..]),
+    None => (digits, ""),
+  };
+
+  let mut all_digits = format!("{}{}", whole, fraction);
+  let mut point = whole.len() as i32 + places;
+  if point < 0 {
+    all_digits = format!("{}{}", "0".repeat(-point as usize), all_digits);
+    point = 0;
+  }
+  while all_digits.len() < point as usize {
+    all_digits.push('0');
+  }
+
+  let (new_whole, new_fraction) = all_digits.split_at(point as usize);
+  let new_whole = new_whole.trim_start_matches('0');
+  let new_fraction = new_fraction.trim_end_matches('0');
+  let new_whole = if new_whole.is_empty() { "0" } else { new_whole };
+  if new_fraction.is_empty() {
+    format!("{}{}", sign, new_whole)
+  } else {
+    format!("{}{}.{}", sign, new_whole, new_fraction)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn byte_sizes_parse_with_suffixes() {
+    assert_eq!(ByteSize::parse_from_str("10k"), Ok(ByteSize::new(10_000)));
+    assert_eq!(ByteSize::parse_from_str("4MiB"), Ok(ByteSize::new(4 << 20)));
+    assert_eq!(ByteSize::parse_from_str("1.5GB"), Ok(ByteSize::new(1_500_000_000)));
+    assert_eq!(ByteSize::parse_from_str("512 mib"), Ok(ByteSize::new(512 << 20)));
+    assert_eq!(ByteSize::parse_from_str("1.5KiB"), Ok(ByteSize::new(1536)));
+    assert_eq!(ByteSize::parse_from_str("42"), Ok(ByteSize::new(42)));
+    assert_eq!(ByteSize::parse_from_str("42B"), Ok(ByteSize::new(42)));
+  }
+
+  #[test]
+  fn malformed_byte_sizes_are_rejected() {
+    let err = |s: &str| {
+      let e = ByteSize::parse_from_str(s).unwrap_err();
+      (e.to_string(), e.span())
+    };
+
+    assert_eq!(err("0.5B"), ("`0.5B` is not a whole number of bytes".to_owned(), 0..3));
+    assert_eq!(err("20EiB"), (
+      "unknown size suffix `EiB`, expected one of: k, M, G, T, P, Ki, Mi, Gi, Ti, Pi, optionally followed by B"
+        .to_owned(), 2..5));
+    assert_eq!(err("MiB"), ("invalid size `MiB`, expected a number and a suffix, like `512MiB`".to_owned(), 0..0));
+    assert_eq!(err("100000PiB"), ("`100000PiB` is too large".to_owned(), 0..9));
+  }
+
+  #[test]
+  fn byte_sizes_round_trip() {
+    for s in &["0B", "512MiB", "10kB", "10k", "1.5GB", "1500MB", "1000KiB", "1.25Ki", "7B", "42"] {
+      let size = ByteSize::parse_from_str(s).unwrap();
+      assert_eq!(ByteSize::display_value(&size), Some(s.to_string()));
+    }
+    assert_eq!(ByteSize::parse_from_str("512 mib").unwrap().to_string(), "512MiB".to_owned());
+    let unparsed = [ByteSize::new(0), ByteSize::new(1 << 29), ByteSize::new(1_500_000_000)];
+    assert_eq!(unparsed.iter().map(|size| size.to_string()).collect::<Vec<String>>(),
+               vec!["0B".to_owned(), "512MiB".to_owned(), "1500MB".to_owned()]);
+  }
+
+  #[test]
+  fn proportions_parse_as_percentages_or_fractions() {
+    assert_eq!(Percent::parse_from_str("75%"), Ok(Percent::new(0.75)));
+    assert_eq!(Percent::parse_from_str("0.75"), Ok(Percent::new(0.75)));
+    assert_eq!(Percent::parse_from_str("7%"), Ok(Percent::new(0.07)));
+    assert_eq!(Ratio::parse_from_str("150%"), Ok(Ratio::new(1.5)));
+    assert_eq!(Percent::parse_from_str("75 percent").unwrap_err().to_string(),
+               "invalid proportion `75 percent`, expected a percentage like `75%` or a fraction like `0.75`");
+  }
+
+  #[test]
+  fn malformed_proportions_are_rejected() {
+    let err = |s: &str| Percent::parse_from_str(s).unwrap_err().to_string();
+
+    for s in &["-", ".", "-.", "%", "-%", ".%", "--5", "1.2.3", "+5"] {
+      assert_eq!(err(s), format!(
+        "invalid proportion `{}`, expected a percentage like `75%` or a fraction like `0.75`", s));
+    }
+    assert_eq!(err("-50%"), "proportion `-50%` is negative".to_owned());
+    assert_eq!(err("-0.5"), "proportion `-0.5` is negative".to_owned());
+    assert_eq!(Ratio::parse_from_str("-0").unwrap_err().to_string(), "proportion `-0` is negative".to_owned());
+  }
+
+  #[test]
+  fn proportions_round_trip() {
+    for s in &["75%", "7%", "0.5%", "150%", "0%"] {
+      let percent = Percent::parse_from_str(s).unwrap();
+      assert_eq!(Percent::display_value(&percent), Some(s.to_string()));
+    }
+    for s in &["0.75", "1.5", "0"] {
+      let ratio = Ratio::parse_from_str(s).unwrap();
+      assert_eq!(Percent::display_value(&Percent::parse_from_str(s).unwrap()), Some(s.to_string()));
+      assert_eq!(Ratio::display_value(&ratio), Some(s.to_string()));
+    }
+    assert_eq!(Ratio::display_value(&Ratio::parse_from_str("7%").unwrap()), Some("7%".to_owned()));
+    assert_eq!((Percent::new(0.07).to_string(), Ratio::new(0.07).to_string()),
+               ("7%".to_owned(), "0.07".to_owned()));
+  }
+}
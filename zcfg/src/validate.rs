@@ -0,0 +1,139 @@
+/*!
+ * Built-in validators for use in the `validate: [...]` clause of `define_cfg!`.
+ *
+ * A validator is anything callable as `Fn(&T) -> Result<(), String>`, so plain functions work
+ * as well as the constructors in this module. Validators are applied to the default value when the
+ * config is registered, and to every value set through its `ConfigInitializer`. The parsers refuse
+ * to apply anything while a default is invalid, as listed by `zcfg::invalid_defaults`. Each config builds
+ * its validators once and shares them between threads, so they must be `Send + Sync + 'static`.
+ *
+ * Because the clause is expanded inside the config's module, refer to validators by absolute or
+ * `super::` paths:
+ *
+ * ```ignore
+ * define_cfg!(net_protocol_timeout_ms, u32, 20000, "...",
+ *             validate: [::zcfg::validate::range(1, 60000)]);
+ * define_cfg!(net_host, String, "localhost".to_owned(), "...",
+ *             validate: [::zcfg::validate::non_empty(), super::check_host]);
+ * ```
+ */
+
+use std::fmt::Debug;
+use std::fmt::Display;
+
+/** A validator for values of type `T`, as stored by `define_cfg!`. */
+pub type Validator<T> = Box<dyn Fn(&T) -> Result<(), String> + Send + Sync>;
+
+/** Boxes a validator, pinning down its argument type so closures in the clause need no annotation. */
+#[doc(hidden)]
+pub fn __boxed_in_macro_do_not_use_elsewhere<T, F>(validator: F) -> Validator<T>
+    where F: Fn(&T) -> Result<(), String> + Send + Sync + 'static {
+  Box::new(validator)
+}
+
+/** Requires the value to lie within `[min, max]`, inclusive. */
+pub fn range<T>(min: T, max: T) -> impl Fn(&T) -> Result<(), String>
+    where T: PartialOrd + Display {
+  move |value: &T| {
+    if *value < min || *value > max {
+      Err(format!("{} is outside the range [{}, {}]", value, min, max))
+    } else {
+      Ok(())
+    }
+  }
+}
+
+/** Requires a string or collection to have at least one element. */
+pub fn non_empty<T>() -> impl Fn(&T) -> Result<(), String>
+    where T: HasLength {
+  |value: &T| {
+    if value.length() == 0 {
+      Err("value must not be empty".to_owned())
+    } else {
+      Ok(())
+    }
+  }
+}
+
+/** Requires the value to equal one of `allowed`. */
+pub fn one_of<T, A>(allowed: &'static [A]) -> impl Fn(&T) -> Result<(), String>
+    where T: PartialEq<A> + Debug, A: Debug {
+  move |value: &T| {
+    if allowed.iter().any(|a| *value == *a) {
+      Ok(())
+    } else {
+      Err(format!("{:?} is not one of {:?}", value, allowed))
+    }
+  }
+}
+
+/**
+ * Requires the entire value to match the regular expression `re`.
+ *
+ * An invalid expression fails every value, so it is reported when the default is validated.
+ */
+#[cfg(feature = "regex")]
+pub fn pattern<T>(re: &str) -> impl Fn(&T) -> Result<(), String>
+    where T: AsRef<str> {
+  let compiled = ::regex::Regex::new(&format!("^(?:{})$", re)).map_err(|e| e.to_string());
+  let re = re.to_owned();
+  move |value: &T| {
+    match compiled {
+      Err(ref e) => Err(format!("invalid pattern /{}/: {}", re, e)),
+      Ok(ref compiled) if compiled.is_match(value.as_ref()) => Ok(()),
+      Ok(_) => Err(format!("{:?} does not match /{}/", value.as_ref(), re)),
+    }
+  }
+}
+
+/** Any value with a notion of emptiness, for use with `non_empty`. */
+pub trait HasLength {
+  fn length(&self) -> usize;
+}
+
+impl HasLength for String {
+  fn length(&self) -> usize {
+    self.len()
+  }
+}
+
+impl <T> HasLength for Vec<T> {
+  fn length(&self) -> usize {
+    self.len()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn range_is_inclusive() {
+    let v = range(0, 10);
+    assert_eq!(v(&0), Ok(()));
+    assert_eq!(v(&10), Ok(()));
+    assert_eq!(v(&-1), Err("-1 is outside the range [0, 10]".to_owned()));
+  }
+
+  #[test]
+  fn non_empty_rejects_empty_values() {
+    assert_eq!(non_empty()(&"a".to_owned()), Ok(()));
+    assert_eq!(non_empty()(&String::new()), Err("value must not be empty".to_owned()));
+    assert_eq!(non_empty()(&Vec::<u32>::new()), Err("value must not be empty".to_owned()));
+  }
+
+  #[test]
+  fn one_of_compares_across_types() {
+    let v = one_of(&["tcp", "udp"]);
+    assert_eq!(v(&"tcp".to_owned()), Ok(()));
+    assert_eq!(v(&"icmp".to_owned()), Err("\"icmp\" is not one of [\"tcp\", \"udp\"]".to_owned()));
+  }
+
+  #[cfg(feature = "regex")]
+  #[test]
+  fn pattern_matches_whole_value() {
+    let v = pattern("[a-z]+");
+    assert_eq!(v(&"abc".to_owned()), Ok(()));
+    assert_eq!(v(&"abc1".to_owned()), Err("\"abc1\" does not match /[a-z]+/".to_owned()));
+  }
+}
@@ -0,0 +1,40 @@
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate zcfg;
+
+use zcfg::Candidate;
+use zcfg::ConfigLoader;
+use zcfg::InitErr;
+use zcfg::LoadErr;
+use zcfg::ValueSource;
+
+// An invalid default fails every load, so it gets a test binary of its own.
+define_cfg!(invalid_default_1, u32, 0u32, "some invalid_default_1 configuration",
+            validate: [::zcfg::validate::range(1, 10)]);
+define_cfg!(invalid_default_2, u32, 0u32, "some invalid_default_2 configuration");
+
+#[test]
+fn invalid_defaults_are_reported_instead_of_aborting() {
+  let expected = InitErr::ValidationFailed {
+    config_name: "invalid_default_1".to_owned(),
+    err: "0 is outside the range [1, 10]".to_owned(),
+  };
+  assert_eq!(invalid_default_1::INITIALIZER.default_err(), Some(&expected));
+  assert_eq!(zcfg::invalid_defaults(), vec![expected]);
+
+  let errs = ConfigLoader::new()
+    .source(vec![Candidate::new("invalid_default_2".to_owned(), "2".to_owned(), ValueSource::CommandLine)])
+    .load()
+    .unwrap_err();
+
+  match errs.as_slice() {
+    [LoadErr::InvalidDefault(InitErr::ValidationFailed { config_name, .. })] => {
+      assert_eq!(config_name, "invalid_default_1")
+    },
+    _ => panic!("unexpected errors {:?}", errs),
+  }
+  assert_eq!(errs[0].to_string(),
+             "invalid default: value for `invalid_default_1` failed validation: 0 is outside the range [1, 10]");
+  assert_eq!(invalid_default_2::CONFIG.source(), ValueSource::Default);
+}
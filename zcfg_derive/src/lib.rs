@@ -0,0 +1,401 @@
+/*!
+ * `#[derive(ConfigParseable)]` for enums and structs, and the `#[zcfg::test]` attribute, which is
+ * described in the `test_attr` module.
+ *
+ * ```ignore
+ * #[derive(Clone, ConfigParseable)]
+ * enum BuildStrategy {
+ *   Local,
+ *   #[zcfg(rename = "remote-exec")]
+ *   Remote(String),
+ *   Cluster { addr: String, port: u32 },
+ * }
+ * ```
+ *
+ * accepts `local`, `remote-exec(build.example.com)` and `Cluster(addr=10.0.0.1;port=8080)`.
+ *
+ * - Unit variants are written as their name, which is matched case-insensitively.
+ * - Tuple variants are written as `Variant(value)`, with multiple values separated by `;`.
+ * - Struct variants, and plain structs, take `key=value` fields separated by `;`. A struct variant
+ *   wraps them in parentheses.
+ *
+ * `#[zcfg(rename = "...")]` changes the name of a variant or field, and `#[zcfg(default)]` lets a
+ * named field be omitted in favor of its `Default` value.
+ *
+ * Every field type must implement `zcfg::ConfigParseable` with itself as the `Output`. Values are
+ * rendered back into the same syntax for help text.
+ */
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+mod test_attr;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::Attribute;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Fields;
+use syn::Ident;
+use syn::LitStr;
+use syn::Type;
+
+#[proc_macro_derive(ConfigParseable, attributes(zcfg))]
+pub fn derive_config_parseable(input: TokenStream) -> TokenStream {
+  match syn::parse::<DeriveInput>(input).and_then(|input| expand(&input)) {
+    Ok(tokens) => tokens.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+  match test_attr::expand(args.into(), item.into()) {
+    Ok(tokens) => tokens.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+  if !input.generics.params.is_empty() {
+    return Err(syn::Error::new_spanned(&input.generics, "ConfigParseable cannot be derived for generic types"))
+  }
+  let attrs = ZcfgAttrs::parse(&input.attrs)?;
+  if attrs.rename.is_some() || attrs.default {
+    return Err(syn::Error::new_spanned(&input.ident, "zcfg attributes belong on variants and fields"))
+  }
+
+  let name = &input.ident;
+  let (parse_body, display_body) = match input.data {
+    Data::Enum(ref data) => {
+      let variants = data.variants.iter()
+        .map(|v| Ok((v, VariantSpec::new(&v.attrs, &v.ident, &v.fields)?)))
+        .collect::<syn::Result<Vec<_>>>()?;
+      check_unique_names(variants.iter().map(|(v, spec)| (&v.ident, &spec.name)))?;
+      expand_enum(name, &variants)
+    },
+    Data::Struct(ref data) => {
+      let spec = VariantSpec::new(&[], name, &data.fields)?;
+      expand_struct(name, &spec)
+    },
+    Data::Union(_) => return Err(syn::Error::new_spanned(name, "ConfigParseable cannot be derived for unions")),
+  };
+
+  Ok(quote! {
+    impl ::zcfg::ConfigParseable for #name {
+      type Output = #name;
+
+      fn parse_from_str(s: &str) -> ::std::result::Result<#name, ::zcfg::ParseErr> {
+        #parse_body
+      }
+
+      fn display_value(value: &#name) -> ::std::option::Option<::std::string::String> {
+        #display_body
+      }
+    }
+  })
+}
+
+/** The options set by `#[zcfg(...)]` attributes. */
+#[derive(Default)]
+struct ZcfgAttrs {
+  rename: Option<String>,
+  default: bool,
+}
+
+impl ZcfgAttrs {
+  fn parse(attrs: &[Attribute]) -> syn::Result<ZcfgAttrs> {
+    let mut zcfg_attrs = ZcfgAttrs::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("zcfg")) {
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("rename") {
+          zcfg_attrs.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+          Ok(())
+        } else if meta.path.is_ident("default") {
+          zcfg_attrs.default = true;
+          Ok(())
+        } else {
+          Err(meta.error("expected `rename = \"...\"` or `default`"))
+        }
+      })?;
+    }
+    Ok(zcfg_attrs)
+  }
+}
+
+/** A struct, or an enum variant, and the name it is written as. */
+struct VariantSpec {
+  name: String,
+  shape: Shape,
+}
+
+enum Shape {
+  Unit,
+  Tuple(Vec<Type>),
+  Named(Vec<FieldSpec>),
+}
+
+struct FieldSpec {
+  ident: Ident,
+  key: String,
+  ty: Type,
+  default: bool,
+}
+
+impl VariantSpec {
+  fn new(attrs: &[Attribute], ident: &Ident, fields: &Fields) -> syn::Result<VariantSpec> {
+    let attrs = ZcfgAttrs::parse(attrs)?;
+    if attrs.default {
+      return Err(syn::Error::new_spanned(ident, "`default` belongs on named fields"))
+    }
+
+    let shape = match *fields {
+      Fields::Unit => Shape::Unit,
+      Fields::Unnamed(ref fields) => {
+        for field in fields.unnamed.iter() {
+          let field_attrs = ZcfgAttrs::parse(&field.attrs)?;
+          if field_attrs.rename.is_some() || field_attrs.default {
+            return Err(syn::Error::new_spanned(field, "zcfg attributes are not supported on tuple fields"))
+          }
+        }
+        Shape::Tuple(fields.unnamed.iter().map(|f| f.ty.clone()).collect())
+      },
+      Fields::Named(ref fields) => {
+        let fields = fields.named.iter()
+          .map(|field| {
+            let field_attrs = ZcfgAttrs::parse(&field.attrs)?;
+            let ident = field.ident.clone().expect("named fields have idents");
+            Ok(FieldSpec {
+              key: field_attrs.rename.unwrap_or_else(|| ident.to_string()),
+              ident,
+              ty: field.ty.clone(),
+              default: field_attrs.default,
+            })
+          })
+          .collect::<syn::Result<Vec<FieldSpec>>>()?;
+        check_unique_names(fields.iter().map(|f| (&f.ident, &f.key)))?;
+        Shape::Named(fields)
+      },
+    };
+
+    Ok(VariantSpec {
+      name: attrs.rename.unwrap_or_else(|| ident.to_string()),
+      shape,
+    })
+  }
+
+  /** How the variant is written, such as `Cluster(addr=String;port=u32)`, for error messages. */
+  fn syntax(&self) -> String {
+    let type_name = |ty: &Type| quote!(#ty).to_string().replace(' ', "");
+    match self.shape {
+      Shape::Unit => self.name.clone(),
+      Shape::Tuple(ref types) => {
+        format!("{}({})", self.name, types.iter().map(type_name).collect::<Vec<String>>().join(";"))
+      },
+      Shape::Named(ref fields) => {
+        let fields = fields.iter()
+          .map(|f| format!("{}={}", f.key, type_name(&f.ty)))
+          .collect::<Vec<String>>();
+        format!("{}({})", self.name, fields.join(";"))
+      },
+    }
+  }
+
+  /** The binding for each field, in declaration order. */
+  fn bindings(&self) -> Vec<Ident> {
+    let count = match self.shape {
+      Shape::Unit => 0,
+      Shape::Tuple(ref types) => types.len(),
+      Shape::Named(ref fields) => fields.len(),
+    };
+    (0..count).map(|idx| Ident::new(&format!("__field_{}", idx), Span::call_site())).collect()
+  }
+
+  /** A pattern for `path` that binds each field by reference. */
+  fn pattern(&self, path: &TokenStream2) -> TokenStream2 {
+    let bindings = self.bindings();
+    match self.shape {
+      Shape::Unit => quote!(#path),
+      Shape::Tuple(_) => quote!(#path( #(ref #bindings),* )),
+      Shape::Named(ref fields) => {
+        let idents = fields.iter().map(|f| &f.ident);
+        quote!(#path { #(#idents: ref #bindings),* })
+      },
+    }
+  }
+
+  /** An expression that constructs `path` from the fields in `args`, a `&str`. */
+  fn construct(&self, path: &TokenStream2) -> TokenStream2 {
+    match self.shape {
+      Shape::Unit => quote!(#path),
+      Shape::Tuple(ref types) => {
+        let count = types.len();
+        let keys = (0..count).map(|idx| idx.to_string());
+        let idxs = 0..count;
+        quote!({
+          let values = ::zcfg::derive_support::tuple_values(s, args, #count)?;
+          #path( #(::zcfg::derive_support::parse_field::<#types>(s, #keys, values[#idxs])?),* )
+        })
+      },
+      Shape::Named(ref fields) => {
+        let keys = fields.iter().map(|f| &f.key);
+        let values = fields.iter().enumerate().map(|(idx, f)| {
+          let (key, ty) = (&f.key, &f.ty);
+          if f.default {
+            quote! {
+              match values[#idx] {
+                ::std::option::Option::Some(value) => ::zcfg::derive_support::parse_field::<#ty>(s, #key, value)?,
+                ::std::option::Option::None => <#ty as ::std::default::Default>::default(),
+              }
+            }
+          } else {
+            quote!(::zcfg::derive_support::parse_required_field::<#ty>(s, #key, values[#idx])?)
+          }
+        });
+        let idents = fields.iter().map(|f| &f.ident);
+        quote!({
+          let values = ::zcfg::derive_support::key_values(s, args, &[#(#keys),*])?;
+          #path { #(#idents: #values),* }
+        })
+      },
+    }
+  }
+
+  /** An expression rendering the fields bound by `pattern` as `Option<String>`. */
+  fn display_fields(&self) -> TokenStream2 {
+    let bindings = self.bindings();
+    match self.shape {
+      Shape::Unit => quote!(::std::option::Option::Some(::std::string::String::new())),
+      Shape::Tuple(ref types) => quote! {
+        ::zcfg::derive_support::join_fields(vec![
+          #(<#types as ::zcfg::ConfigParseable>::display_value(#bindings)),*
+        ])
+      },
+      Shape::Named(ref fields) => {
+        let keys = fields.iter().map(|f| &f.key);
+        let types = fields.iter().map(|f| &f.ty);
+        quote! {
+          ::zcfg::derive_support::join_fields(vec![
+            #(<#types as ::zcfg::ConfigParseable>::display_value(#bindings)
+                .map(|value| format!("{}={}", #keys, value))),*
+          ])
+        }
+      },
+    }
+  }
+}
+
+fn expand_enum(name: &Ident, variants: &[(&syn::Variant, VariantSpec)]) -> (TokenStream2, TokenStream2) {
+  let valid_variants = variants.iter()
+    .map(|(_, spec)| spec.syntax())
+    .collect::<Vec<String>>()
+    .join(", ");
+
+  let parse_arms = variants.iter().map(|(variant, spec)| {
+    let variant_name = &spec.name;
+    let path = { let ident = &variant.ident; quote!(#name::#ident) };
+    let construct = spec.construct(&path);
+    let with_args = match spec.shape {
+      Shape::Unit => quote! {
+        ::std::option::Option::None => ::std::result::Result::Ok(#construct),
+        ::std::option::Option::Some(args) => {
+          let message = format!("variant `{}` takes no value", #variant_name);
+          ::std::result::Result::Err(::zcfg::derive_support::err_in(s, args, message))
+        },
+      },
+      Shape::Tuple(_) => {
+        let syntax = spec.syntax();
+        quote! {
+          ::std::option::Option::None => {
+            let message = format!("variant `{}` expects a value, as in `{}`", #variant_name, #syntax);
+            ::std::result::Result::Err(::zcfg::ParseErr::new(s, message))
+          },
+          ::std::option::Option::Some(args) => ::std::result::Result::Ok(#construct),
+        }
+      },
+      Shape::Named(_) => quote! {
+        args => {
+          let args = args.unwrap_or("");
+          ::std::result::Result::Ok(#construct)
+        },
+      },
+    };
+    quote! {
+      if variant_name.eq_ignore_ascii_case(#variant_name) {
+        return match args {
+          #with_args
+        }
+      }
+    }
+  });
+
+  let display_arms = variants.iter().map(|(variant, spec)| {
+    let variant_name = &spec.name;
+    let pattern = spec.pattern(&{ let ident = &variant.ident; quote!(#name::#ident) });
+    match spec.shape {
+      Shape::Unit => quote!(#pattern => ::std::option::Option::Some(#variant_name.to_owned()),),
+      _ => {
+        let fields = spec.display_fields();
+        quote!(#pattern => #fields.map(|fields| format!("{}({})", #variant_name, fields)),)
+      },
+    }
+  });
+
+  let parse_body = quote! {
+    let (variant_name, args) = ::zcfg::derive_support::split_variant(s)?;
+    #(#parse_arms)*
+    let message = format!("unknown variant `{}`", variant_name);
+    ::std::result::Result::Err(
+      ::zcfg::derive_support::err_in(s, variant_name, message).expecting(concat!("one of: ", #valid_variants)))
+  };
+  let display_body = quote! {
+    match *value {
+      #(#display_arms)*
+    }
+  };
+  (parse_body, display_body)
+}
+
+fn expand_struct(name: &Ident, spec: &VariantSpec) -> (TokenStream2, TokenStream2) {
+  let path = quote!(#name);
+  let construct = spec.construct(&path);
+  let parse_body = match spec.shape {
+    Shape::Unit => quote! {
+      if s.is_empty() {
+        ::std::result::Result::Ok(#construct)
+      } else {
+        ::std::result::Result::Err(::zcfg::ParseErr::new(s, format!("`{}` takes no value", stringify!(#name))))
+      }
+    },
+    _ => quote! {
+      let args = s;
+      ::std::result::Result::Ok(#construct)
+    },
+  };
+
+  let pattern = spec.pattern(&path);
+  let fields = spec.display_fields();
+  let display_body = quote! {
+    match *value {
+      #pattern => #fields,
+    }
+  };
+  (parse_body, display_body)
+}
+
+/** Rejects names that would be ambiguous when matched case-insensitively. */
+fn check_unique_names<'a, I: Iterator<Item = (&'a Ident, &'a String)>>(names: I) -> syn::Result<()> {
+  let mut seen: Vec<&String> = Vec::new();
+  for (ident, name) in names {
+    if seen.iter().any(|s| s.eq_ignore_ascii_case(name)) {
+      return Err(syn::Error::new_spanned(ident, format!("`{}` is used by more than one variant or field", name)))
+    }
+    seen.push(name);
+  }
+  Ok(())
+}
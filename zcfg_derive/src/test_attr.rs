@@ -0,0 +1,111 @@
+/*!
+ * `#[zcfg::test(...)]`, which overlays config values for the duration of a test.
+ *
+ * ```ignore
+ * #[zcfg::test(greeting = "Hi", self::net::timeout_ms = 5)]
+ * fn greets_quickly() {
+ *   ...
+ * }
+ * ```
+ *
+ * The attribute replaces `#[test]`. Each value is written as a literal, and parsed by the config's
+ * `ConfigParseable` exactly as if it were given on the command line. The values are applied with
+ * `Config::overlay_for_test` before the body runs, so they are only seen by the test's thread, and
+ * are removed when it returns or panics.
+ *
+ * A config named by a path, like `self::net::timeout_ms`, is resolved by the compiler, so a typo is
+ * a compile error. A config named by a bare identifier may be defined anywhere in the binary, and
+ * is looked up by name when the test starts, which fails the test with `LoadErr::UndefinedArg`
+ * if no config has that name.
+ */
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::Expr;
+use syn::ItemFn;
+use syn::Lit;
+use syn::MetaNameValue;
+use syn::Path;
+use syn::Token;
+use syn::UnOp;
+
+pub fn expand(args: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+  let assignments = Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse2(args)?;
+  let test_fn = syn::parse2::<ItemFn>(item)?;
+  if !test_fn.sig.inputs.is_empty() || test_fn.sig.asyncness.is_some() {
+    return Err(syn::Error::new_spanned(&test_fn.sig, "#[zcfg::test] functions take no arguments and are not async"))
+  }
+
+  let mut names = Vec::new();
+  let mut overlays = Vec::new();
+  for assignment in assignments.iter() {
+    let name = config_name(&assignment.path);
+    if names.contains(&name) {
+      return Err(syn::Error::new_spanned(&assignment.path, format!("`{}` is set more than once", name)))
+    }
+    let value = literal_value(&assignment.value)?;
+    overlays.push(expand_overlay(&assignment.path, &name, &value));
+    names.push(name);
+  }
+
+  let ItemFn { attrs, vis, sig, block } = test_fn;
+  // Overlays are dropped after the body, or while unwinding from it.
+  let apply_overlays = if overlays.is_empty() {
+    quote!()
+  } else {
+    quote!(let _zcfg_overlays = [#(#overlays),*];)
+  };
+  // The built-in attribute is named by path, since `use zcfg::*` brings this one in as `test`.
+  Ok(quote! {
+    #[::std::prelude::v1::test]
+    #(#attrs)*
+    #vis #sig {
+      #apply_overlays
+      #block
+    }
+  })
+}
+
+fn expand_overlay(path: &Path, name: &str, value: &str) -> TokenStream2 {
+  if path.segments.len() > 1 || path.leading_colon.is_some() {
+    quote! {
+      #path::INITIALIZER.overlay_for_test(#value)
+        .unwrap_or_else(|e| panic!("#[zcfg::test]: failed to set `{}`: {}", #name, e))
+    }
+  } else {
+    quote! {
+      ::zcfg::overlay_candidate(::zcfg::Candidate::new(
+        #name.to_owned(), #value.to_owned(), ::zcfg::ValueSource::Testing))
+        .unwrap_or_else(|e| panic!("#[zcfg::test]: {}", e))
+    }
+  }
+}
+
+fn config_name(path: &Path) -> String {
+  path.segments.last().map(|segment| segment.ident.to_string()).unwrap_or_default()
+}
+
+/** Renders a literal the way it would be written as a flag value. */
+fn literal_value(expr: &Expr) -> syn::Result<String> {
+  match *expr {
+    Expr::Lit(ref lit) => match lit.lit {
+      Lit::Str(ref s) => Ok(s.value()),
+      Lit::Char(ref c) => Ok(c.value().to_string()),
+      Lit::Bool(ref b) => Ok(b.value.to_string()),
+      Lit::Int(ref i) => Ok(i.base10_digits().to_owned()),
+      Lit::Float(ref f) => Ok(f.base10_digits().to_owned()),
+      _ => Err(syn::Error::new_spanned(expr, "config values must be strings, numbers, chars or bools")),
+    },
+    Expr::Unary(ref unary) => match unary.op {
+      UnOp::Neg(_) => match *unary.expr {
+        Expr::Lit(ref lit) if matches!(lit.lit, Lit::Int(_) | Lit::Float(_)) => {
+          Ok(format!("-{}", literal_value(&unary.expr)?))
+        },
+        _ => Err(syn::Error::new_spanned(expr, "only numbers can be negated")),
+      },
+      _ => Err(syn::Error::new_spanned(expr, "config values must be literals")),
+    },
+    _ => Err(syn::Error::new_spanned(expr, "config values must be literals")),
+  }
+}
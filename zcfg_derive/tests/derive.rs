@@ -0,0 +1,123 @@
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate zcfg;
+#[macro_use]
+extern crate zcfg_derive;
+
+use zcfg::ConfigParseable;
+
+#[derive(Clone, Debug, PartialEq, ConfigParseable)]
+pub enum BuildStrategy {
+  Local,
+  #[zcfg(rename = "remote-exec")]
+  Remote(String),
+  Pair(u32, u32),
+  Cluster { addr: String, #[zcfg(default)] port: u32 },
+}
+
+#[derive(Clone, Debug, PartialEq, ConfigParseable)]
+pub struct Endpoint {
+  host: String,
+  #[zcfg(rename = "p")]
+  port: u32,
+  strategy: BuildStrategy,
+  #[zcfg(default)]
+  tags: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, ConfigParseable)]
+struct Marker;
+
+define_cfg!(derived_strategy, super::BuildStrategy, super::BuildStrategy::Local,
+            "some derived_strategy configuration");
+
+fn parse<T: ConfigParseable>(s: &str) -> Result<T::Output, String> {
+  T::parse_from_str(s).map_err(|e| e.to_string())
+}
+
+#[test]
+fn unit_variants_match_case_insensitively() {
+  assert_eq!(parse::<BuildStrategy>("local"), Ok(BuildStrategy::Local));
+  assert_eq!(parse::<BuildStrategy>("LOCAL"), Ok(BuildStrategy::Local));
+  assert_eq!(parse::<BuildStrategy>("Local(x)"), Err("variant `Local` takes no value".to_owned()));
+}
+
+#[test]
+fn variants_with_fields_take_arguments() {
+  assert_eq!(parse::<BuildStrategy>("remote-exec(build.example.com)"),
+             Ok(BuildStrategy::Remote("build.example.com".to_owned())));
+  assert_eq!(parse::<BuildStrategy>("pair(1;2)"), Ok(BuildStrategy::Pair(1, 2)));
+  assert_eq!(parse::<BuildStrategy>("Cluster(addr=10.0.0.1;port=8080)"),
+             Ok(BuildStrategy::Cluster { addr: "10.0.0.1".to_owned(), port: 8080 }));
+  assert_eq!(parse::<BuildStrategy>("Cluster(addr=10.0.0.1)"),
+             Ok(BuildStrategy::Cluster { addr: "10.0.0.1".to_owned(), port: 0 }));
+}
+
+#[test]
+fn errors_list_valid_variants() {
+  assert_eq!(parse::<BuildStrategy>("Remote(x)"), Err(
+    "unknown variant `Remote`, expected one of: \
+     Local, remote-exec(String), Pair(u32;u32), Cluster(addr=String;port=u32)".to_owned()));
+  assert_eq!(parse::<BuildStrategy>("Pair(1)"),
+             Err("expected 2 values separated by `;`, found 1".to_owned()));
+  assert_eq!(parse::<BuildStrategy>("Pair"),
+             Err("variant `Pair` expects a value, as in `Pair(u32;u32)`".to_owned()));
+  assert_eq!(parse::<BuildStrategy>("Cluster(port=1)"), Err("missing field `addr`".to_owned()));
+}
+
+#[test]
+fn errors_locate_the_bad_part() {
+  let err = BuildStrategy::parse_from_str("Pair(1;x)").unwrap_err();
+  assert_eq!((err.span(), err.cause().map(|e| e.input())), (7..8, Some("x")));
+  assert_eq!(BuildStrategy::parse_from_str("Nope(1)").unwrap_err().underline(), "Nope(1)\n^^^^".to_owned());
+}
+
+#[test]
+fn structs_take_key_values() {
+  assert_eq!(parse::<Endpoint>("host=localhost;p=80;strategy=Cluster(addr=a;port=1)"), Ok(Endpoint {
+    host: "localhost".to_owned(),
+    port: 80,
+    strategy: BuildStrategy::Cluster { addr: "a".to_owned(), port: 1 },
+    tags: Vec::new(),
+  }));
+  assert_eq!(parse::<Endpoint>("host=localhost;p=x;strategy=local"),
+             Err("field `p`: invalid digit found in string, expected u32".to_owned()));
+  assert_eq!(parse::<Endpoint>("host=localhost;port=80"),
+             Err("unknown field `port`, expected one of: host, p, strategy, tags".to_owned()));
+  assert_eq!(parse::<Marker>(""), Ok(Marker));
+}
+
+#[test]
+fn values_round_trip() {
+  let values = vec![
+    BuildStrategy::Local,
+    BuildStrategy::Remote("build.example.com".to_owned()),
+    BuildStrategy::Pair(1, 2),
+    BuildStrategy::Cluster { addr: "10.0.0.1".to_owned(), port: 8080 },
+  ];
+  for value in values {
+    let displayed = BuildStrategy::display_value(&value).unwrap();
+    assert_eq!(parse::<BuildStrategy>(&displayed), Ok(value));
+  }
+
+  let endpoint = Endpoint {
+    host: "localhost".to_owned(),
+    port: 80,
+    strategy: BuildStrategy::Pair(3, 4),
+    tags: vec!["a".to_owned(), "b".to_owned()],
+  };
+  assert_eq!(Endpoint::display_value(&endpoint),
+             Some("host=localhost;p=80;strategy=Pair(3;4);tags=a,b".to_owned()));
+  assert_eq!(parse::<Endpoint>(&Endpoint::display_value(&endpoint).unwrap()), Ok(endpoint));
+}
+
+#[test]
+fn derived_types_are_configurable() {
+  let initializers = zcfg::STATIC_CONFIG_INITIALIZERS.read().unwrap();
+  let initializer = initializers.iter().find(|i| i.config_name() == "derived_strategy").unwrap();
+
+  assert_eq!(initializer.default_value(), Some("Local"));
+  assert_eq!(initializer.set_statically("remote-exec(host)"), Ok(()));
+  assert_eq!(derived_strategy::CONFIG.get_value(), BuildStrategy::Remote("host".to_owned()));
+}
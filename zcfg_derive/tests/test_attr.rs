@@ -0,0 +1,48 @@
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate zcfg;
+extern crate zcfg_derive;
+
+use std::panic;
+
+define_cfg!(attr_greeting, String, "Hello".to_owned(), "some attr_greeting configuration");
+define_cfg!(attr_timeout_ms, u32, 100u32, "some attr_timeout_ms configuration",
+            validate: [::zcfg::validate::range(1, 1000)]);
+define_cfg!(attr_offset, i32, 0, "some attr_offset configuration");
+define_cfg!(attr_panicking, String, "unset".to_owned(), "some attr_panicking configuration");
+
+#[zcfg_derive::test(attr_greeting = "Hi", self::attr_timeout_ms = 5, attr_offset = -3)]
+fn values_are_parsed_and_overlaid() {
+  assert_eq!(attr_greeting::CONFIG.get_value(), "Hi".to_owned());
+  assert_eq!(attr_timeout_ms::CONFIG.get_value(), 5);
+  assert_eq!(attr_offset::CONFIG.get_value(), -3);
+  assert_eq!(attr_greeting::CONFIG.source(), zcfg::ValueSource::Testing);
+}
+
+#[zcfg_derive::test(attr_greeting = "Bye")]
+fn tests_see_only_their_own_values() {
+  assert_eq!(attr_greeting::CONFIG.get_value(), "Bye".to_owned());
+  assert_eq!(attr_offset::CONFIG.get_value(), 0);
+}
+
+#[zcfg_derive::test(attr_undefined = "x")]
+#[should_panic(expected = "unknown config `attr_undefined`")]
+fn unknown_names_fail_when_the_test_starts() {}
+
+#[zcfg_derive::test(self::attr_timeout_ms = 5000)]
+#[should_panic(expected = "failed to set `attr_timeout_ms`: value for `attr_timeout_ms` failed validation")]
+fn invalid_values_fail_when_the_test_starts() {}
+
+#[zcfg_derive::test(attr_panicking = "set")]
+#[should_panic(expected = "a failing test")]
+fn panicking_test() {
+  assert_eq!(attr_panicking::CONFIG.get_value(), "set".to_owned());
+  panic!("a failing test");
+}
+
+#[test]
+fn values_are_removed_after_a_panic() {
+  assert!(panic::catch_unwind(panicking_test).is_err());
+  assert_eq!(attr_panicking::CONFIG.get_value(), "unset".to_owned());
+}
@@ -0,0 +1,201 @@
+#[cfg(test)]
+#[macro_use]
+extern crate lazy_static;
+#[cfg_attr(test, macro_use)]
+extern crate zcfg;
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use zcfg::Candidate;
+use zcfg::ConfigSource;
+use zcfg::InitErr;
+use zcfg::ValueSource;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EnvInitErr {
+  /** A variable carrying the parser's prefix that matches no config. */
+  UndefinedArg(String),
+  /** A config's default value fails its validators. See `zcfg::invalid_defaults`. */
+  InvalidDefault(InitErr),
+  /** A variable's value was rejected by its config. */
+  InitErr {
+    var: String,
+    err: InitErr,
+  },
+}
+
+impl fmt::Display for EnvInitErr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      EnvInitErr::UndefinedArg(ref var) => write!(f, "unknown environment variable {}", var),
+      EnvInitErr::InvalidDefault(ref err) => write!(f, "invalid default: {}", err),
+      EnvInitErr::InitErr { ref var, ref err } => write!(f, "environment variable {}: {}", var, err),
+    }
+  }
+}
+
+impl Error for EnvInitErr {}
+
+/**
+ * Populates configs from environment variables.
+ *
+ * Each config is read from a variable named after it: the parser's prefix, an underscore, and the
+ * uppercased config name. With the prefix "MYAPP", `net_protocol_timeout_ms` is read from
+ * `MYAPP_NET_PROTOCOL_TIMEOUT_MS`.
+ */
+pub struct EnvParser {
+  prefix: String,
+}
+
+impl EnvParser {
+  /**
+   * Constructs a parser for variables starting with `prefix`.
+   *
+   * An empty prefix reads each config from its bare uppercased name. Since every variable would
+   * then be a candidate, unknown variables are not reported in that case.
+   */
+  pub fn new(prefix: &str) -> EnvParser {
+    EnvParser {
+      prefix: prefix.to_owned(),
+    }
+  }
+
+  /** The variable that the named config is read from. */
+  pub fn var_name(&self, config_name: &str) -> String {
+    format!("{}{}", self.var_prefix(), config_name.to_uppercase())
+  }
+
+  /** Applies variables from the process environment to their matching configs. */
+  pub fn parse_from_env(&self) -> Result<(), Vec<EnvInitErr>> {
+    self.parse_from_vars(env::vars())
+  }
+
+  /**
+   * Applies `(name, value)` variable pairs to their matching configs.
+   *
+   * Variables without the parser's prefix are ignored.
+   */
+  pub fn parse_from_vars<I: IntoIterator<Item = (String, String)>>(&self, vars: I) -> Result<(), Vec<EnvInitErr>> {
+    let (candidates, mut set_errs) = self.collect_candidates(vars);
+
+    let tagged = candidates.into_iter()
+      .map(|candidate| {
+        let var = self.var_name(candidate.config_name());
+        (candidate, var)
+      });
+    match zcfg::apply_candidates(tagged) {
+      Ok(apply_errs) => set_errs.extend(apply_errs.into_iter().map(|(var, err)| EnvInitErr::InitErr { var, err })),
+      Err(invalid_defaults) => return Err(invalid_defaults.into_iter().map(EnvInitErr::InvalidDefault).collect()),
+    }
+
+    if set_errs.is_empty() {
+      Ok(())
+    } else {
+      Err(set_errs)
+    }
+  }
+
+  /**
+   * Finds the config matching each `(name, value)` variable pair, without applying them.
+   *
+   * This is how `EnvParser` acts as a `zcfg::ConfigSource`.
+   */
+  pub fn candidates_from_vars<I: IntoIterator<Item = (String, String)>>(&self, vars: I) -> Result<Vec<Candidate>, Vec<EnvInitErr>> {
+    let (candidates, errs) = self.collect_candidates(vars);
+    if errs.is_empty() {
+      Ok(candidates)
+    } else {
+      Err(errs)
+    }
+  }
+
+  fn collect_candidates<I: IntoIterator<Item = (String, String)>>(&self, vars: I) -> (Vec<Candidate>, Vec<EnvInitErr>) {
+    let initializers = zcfg::STATIC_CONFIG_INITIALIZERS.read()
+      .expect("initializers were poisoned");
+
+    let mut var_name_to_config_name = HashMap::new();
+    for e in initializers.iter() {
+      var_name_to_config_name.insert(self.var_name(e.config_name()), e.config_name());
+    }
+
+    let var_prefix = self.var_prefix();
+    let mut candidates = Vec::new();
+    let mut errs = Vec::new();
+    for (name, value) in vars.into_iter() {
+      match var_name_to_config_name.get(&name) {
+        Some(config_name) => {
+          candidates.push(Candidate::new((*config_name).to_owned(), value, ValueSource::Environment(name)))
+        },
+        None if !var_prefix.is_empty() && name.starts_with(&var_prefix) => {
+          errs.push(EnvInitErr::UndefinedArg(name))
+        },
+        None => {},
+      }
+    }
+    (candidates, errs)
+  }
+
+  fn var_prefix(&self) -> String {
+    if self.prefix.is_empty() || self.prefix.ends_with('_') {
+      self.prefix.clone()
+    } else {
+      format!("{}_", self.prefix)
+    }
+  }
+}
+
+/** Reads candidates from the process environment. */
+impl ConfigSource for EnvParser {
+  fn candidates(&self) -> Result<Vec<Candidate>, Vec<Box<dyn Error>>> {
+    self.candidates_from_vars(env::vars())
+      .map_err(|errs| errs.into_iter().map(|e| Box::new(e) as Box<dyn Error>).collect())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  define_cfg!(env_1, u32, 0u32, "some env_1 configuration");
+  define_cfg!(env_2, String, "".to_owned(), "some env_2 configuration");
+  define_cfg!(env_3, u32, 0u32, "some env_3 configuration");
+
+  fn vars(vars: &[(&str, &str)]) -> HashMap<String, String> {
+    vars.iter().map(|&(k, v)| (k.to_owned(), v.to_owned())).collect()
+  }
+
+  #[test]
+  fn var_names_are_prefixed_and_uppercased() {
+    assert_eq!(EnvParser::new("MYAPP").var_name("net_protocol_timeout_ms"),
+               "MYAPP_NET_PROTOCOL_TIMEOUT_MS".to_owned());
+    assert_eq!(EnvParser::new("MYAPP_").var_name("greeting"), "MYAPP_GREETING".to_owned());
+    assert_eq!(EnvParser::new("").var_name("greeting"), "GREETING".to_owned());
+  }
+
+  #[test]
+  fn matching_vars_are_applied() {
+    let result = EnvParser::new("TEST").parse_from_vars(
+      vars(&[("TEST_ENV_1", "5"), ("TEST_ENV_2", "hi"), ("PATH", "/bin")]));
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(env_1::CONFIG.get_value(), 5);
+    assert_eq!(env_2::CONFIG.get_value(), "hi".to_owned());
+    assert_eq!(env_1::CONFIG.source(), ValueSource::Environment("TEST_ENV_1".to_owned()));
+  }
+
+  #[test]
+  fn unknown_prefixed_vars_are_reported() {
+    let result = EnvParser::new("OTHER").parse_from_vars(
+      vars(&[("OTHER_ENV_4", "5"), ("OTHER_ENV_3", "x")]));
+
+    let mut errs = result.unwrap_err();
+    errs.sort_by_key(|e| e.to_string());
+    assert_eq!(errs.iter().map(|e| e.to_string()).collect::<Vec<String>>(), vec![
+      "environment variable OTHER_ENV_3: failed to parse value for `env_3`: invalid digit found in \
+       string, expected u32".to_owned(),
+      "unknown environment variable OTHER_ENV_4".to_owned(),
+    ]);
+  }
+}
@@ -0,0 +1,279 @@
+#[cfg(test)]
+#[macro_use]
+extern crate lazy_static;
+#[cfg_attr(test, macro_use)]
+extern crate zcfg;
+
+mod syntax;
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use zcfg::Candidate;
+use zcfg::ConfigSource;
+use zcfg::InitErr;
+use zcfg::ValueSource;
+
+/** A position within a config file. */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FileLocation {
+  path: PathBuf,
+  line: usize,
+  column: usize,
+}
+
+impl FileLocation {
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /** The 1-based line number. */
+  pub fn line(&self) -> usize {
+    self.line
+  }
+
+  /** The 1-based column number, counted in chars. */
+  pub fn column(&self) -> usize {
+    self.column
+  }
+}
+
+impl fmt::Display for FileLocation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}:{}", self.path.display(), self.line, self.column)
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FileInitErr {
+  /** The file could not be read. */
+  Io(PathBuf, String),
+  /** The file is malformed. */
+  Syntax(FileLocation, String),
+  /** An entry that matches no config. */
+  UndefinedArg(FileLocation, String),
+  /** A config's default value fails its validators. See `zcfg::invalid_defaults`. */
+  InvalidDefault(InitErr),
+  InitErr(FileLocation, InitErr),
+}
+
+impl fmt::Display for FileInitErr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      FileInitErr::Io(ref path, ref e) => write!(f, "{}: {}", path.display(), e),
+      FileInitErr::Syntax(ref location, ref e) => write!(f, "{}: {}", location, e),
+      FileInitErr::UndefinedArg(ref location, ref name) => {
+        write!(f, "{}: unknown config `{}`", location, name)
+      },
+      FileInitErr::InvalidDefault(ref e) => write!(f, "invalid default: {}", e),
+      FileInitErr::InitErr(ref location, ref e) => write!(f, "{}: {}", location, e),
+    }
+  }
+}
+
+impl Error for FileInitErr {}
+
+/**
+ * Populates configs from a config file of `name = value` entries.
+ *
+ * The format is the flat subset of TOML, and also accepts unquoted INI-style values:
+ *
+ * ```text
+ * # Comments start with '#' or ';'
+ * net_protocol_timeout_ms = 500
+ * greeting = Hello there
+ * greeting_target = "the \"World\""
+ * multigreeting = ["Hi", Hello, 'Hey']
+ * ```
+ *
+ * Arrays are passed to configs in the list syntax understood by `Vec<T>`, so elements may contain
+ * commas, and arrays may nest.
+ */
+pub struct FileParser;
+
+impl Default for FileParser {
+  fn default() -> FileParser {
+    FileParser::new()
+  }
+}
+
+impl FileParser {
+  pub fn new() -> FileParser {
+    FileParser
+  }
+
+  /** Reads the file at `path` and applies each entry to its matching config. */
+  pub fn parse_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Vec<FileInitErr>> {
+    let path = path.as_ref();
+    self.parse_from_str(path, &read_file(path)?)
+  }
+
+  /**
+   * Applies each entry in `contents` to its matching config.
+   *
+   * `path` is only used to report errors.
+   */
+  pub fn parse_from_str<P: AsRef<Path>>(&self, path: P, contents: &str) -> Result<(), Vec<FileInitErr>> {
+    let (candidates, mut set_errs) = self.collect_candidates(path.as_ref(), contents);
+
+    match zcfg::apply_candidates(candidates) {
+      Ok(apply_errs) => set_errs.extend(apply_errs.into_iter().map(|(location, e)| FileInitErr::InitErr(location, e))),
+      Err(invalid_defaults) => return Err(invalid_defaults.into_iter().map(FileInitErr::InvalidDefault).collect()),
+    }
+
+    if set_errs.is_empty() {
+      Ok(())
+    } else {
+      Err(set_errs)
+    }
+  }
+
+  /**
+   * Finds the config matching each entry in `contents`, without applying them.
+   *
+   * `path` is used to report errors, and as the source of each candidate.
+   */
+  pub fn candidates_from_str<P: AsRef<Path>>(&self, path: P, contents: &str) -> Result<Vec<Candidate>, Vec<FileInitErr>> {
+    let (candidates, errs) = self.collect_candidates(path.as_ref(), contents);
+    if errs.is_empty() {
+      Ok(candidates.into_iter().map(|(candidate, _)| candidate).collect())
+    } else {
+      Err(errs)
+    }
+  }
+
+  /**
+   * Pairs each entry that names a known config with its location, and reports the rest.
+   *
+   * A key that appears more than once is a syntax error at each repetition.
+   */
+  fn collect_candidates(&self, path: &Path, contents: &str) -> (Vec<(Candidate, FileLocation)>, Vec<FileInitErr>) {
+    let location = |line, column| FileLocation { path: path.to_owned(), line, column };
+
+    let entries = match syntax::parse_entries(contents) {
+      Ok(entries) => entries,
+      Err(e) => return (Vec::new(), vec![FileInitErr::Syntax(location(e.line, e.column), e.message)]),
+    };
+
+    let initializers = zcfg::STATIC_CONFIG_INITIALIZERS.read()
+      .expect("initializers were poisoned");
+    let config_names = initializers.iter().map(|e| e.config_name()).collect::<HashSet<&str>>();
+
+    let mut first_lines = HashMap::new();
+    let mut candidates = Vec::new();
+    let mut errs = Vec::new();
+    for entry in entries.into_iter() {
+      let entry_location = location(entry.line, entry.column);
+      if let Some(first_line) = first_lines.get(&entry.name) {
+        let message = format!("`{}` is already set on line {}", entry.name, first_line);
+        errs.push(FileInitErr::Syntax(entry_location, message));
+        continue
+      }
+      first_lines.insert(entry.name.clone(), entry.line);
+
+      if config_names.contains(entry.name.as_str()) {
+        let source = ValueSource::File(path.to_owned(), entry.line);
+        candidates.push((Candidate::new(entry.name, entry.value, source), entry_location))
+      } else {
+        errs.push(FileInitErr::UndefinedArg(entry_location, entry.name))
+      }
+    }
+    (candidates, errs)
+  }
+}
+
+/**
+ * A config file, read as a `zcfg::ConfigSource`.
+ *
+ * The file is read when the candidates are collected, using the same format as `FileParser`.
+ */
+pub struct FileSource {
+  path: PathBuf,
+}
+
+impl FileSource {
+  pub fn new<P: AsRef<Path>>(path: P) -> FileSource {
+    FileSource {
+      path: path.as_ref().to_owned(),
+    }
+  }
+}
+
+impl ConfigSource for FileSource {
+  fn candidates(&self) -> Result<Vec<Candidate>, Vec<Box<dyn Error>>> {
+    read_file(&self.path)
+      .and_then(|contents| FileParser::new().candidates_from_str(&self.path, &contents))
+      .map_err(|errs| errs.into_iter().map(|e| Box::new(e) as Box<dyn Error>).collect())
+  }
+}
+
+fn read_file(path: &Path) -> Result<String, Vec<FileInitErr>> {
+  fs::read_to_string(path)
+    .map_err(|e| vec![FileInitErr::Io(path.to_owned(), e.to_string())])
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  define_cfg!(file_1, u32, 0u32, "some file_1 configuration");
+  define_cfg!(file_2, String, "".to_owned(), "some file_2 configuration");
+  define_cfg!(file_3, Vec<String>, Vec::new(), "some file_3 configuration");
+  define_cfg!(file_4, u32, 0u32, "some file_4 configuration");
+
+  #[test]
+  fn entries_are_applied() {
+    let contents = "file_1 = 5\n\
+                    file_2 = \"Hello, there\" # greeting\n\
+                    file_3 = [a, \"b\"]\n";
+
+    assert_eq!(FileParser::new().parse_from_str("test.toml", contents), Ok(()));
+    assert_eq!(file_1::CONFIG.get_value(), 5);
+    assert_eq!(file_2::CONFIG.get_value(), "Hello, there".to_owned());
+    assert_eq!(file_3::CONFIG.get_value(), vec!["a".to_owned(), "b".to_owned()]);
+    assert_eq!(file_2::CONFIG.source(), ValueSource::File(PathBuf::from("test.toml"), 2));
+  }
+
+  #[test]
+  fn errors_report_locations() {
+    let contents = "\n  file_5 = 1\nfile_4 = x\n";
+
+    let errs = FileParser::new().parse_from_str("test.toml", contents).unwrap_err();
+
+    assert_eq!(errs.iter().map(|e| e.to_string()).collect::<Vec<String>>(), vec![
+      "test.toml:2:3: unknown config `file_5`".to_owned(),
+      "test.toml:3:1: failed to parse value for `file_4`: invalid digit found in string, expected u32".to_owned(),
+    ]);
+  }
+
+  #[test]
+  fn syntax_errors_report_locations() {
+    let errs = FileParser::new().parse_from_str("test.toml", "file_4 = [1, 2").unwrap_err();
+
+    assert_eq!(errs.iter().map(|e| e.to_string()).collect::<Vec<String>>(), vec![
+      "test.toml:1:15: unterminated array".to_owned(),
+    ]);
+  }
+
+  #[test]
+  fn repeated_keys_point_at_the_first() {
+    let errs = FileParser::new().candidates_from_str("test.toml", "file_2 = a\n\nfile_2 = b\n").unwrap_err();
+
+    assert_eq!(errs.iter().map(|e| e.to_string()).collect::<Vec<String>>(), vec![
+      "test.toml:3:1: `file_2` is already set on line 1".to_owned(),
+    ]);
+  }
+
+  #[test]
+  fn missing_files_are_reported() {
+    let errs = FileParser::new().parse_file("/nonexistent/zcfg.toml").unwrap_err();
+
+    match errs.as_slice() {
+      [FileInitErr::Io(path, _)] => assert_eq!(path, Path::new("/nonexistent/zcfg.toml")),
+      _ => panic!("unexpected errors {:?}", errs),
+    }
+  }
+}
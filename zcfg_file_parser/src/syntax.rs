@@ -0,0 +1,318 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/** A single `name = value` assignment, with the position of its name. */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Entry {
+  pub name: String,
+  pub value: String,
+  pub line: usize,
+  pub column: usize,
+}
+
+/** A syntax error at a 1-based line and column. */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyntaxErr {
+  pub line: usize,
+  pub column: usize,
+  pub message: String,
+}
+
+/**
+ * Parses every entry in a config file.
+ *
+ * The format is the flat subset of TOML, relaxed to also accept INI-style unquoted values:
+ *
+ * ```text
+ * # Comments run to the end of the line, and may also start with ';'.
+ * net_protocol_timeout_ms = 500
+ * greeting = Hello there
+ * greeting_target = "the \"World\""
+ * multigreeting = ["Hi", Hello,
+ *                  'Hey']
+ * ```
+ *
+ * Arrays become the list syntax understood by `Vec<T>` configs (see `zcfg::list`).
+ */
+pub fn parse_entries(contents: &str) -> Result<Vec<Entry>, SyntaxErr> {
+  let mut cursor = Cursor::new(contents);
+  let mut entries = Vec::new();
+  loop {
+    cursor.skip_blank_lines();
+    match cursor.peek() {
+      None => return Ok(entries),
+      Some('[') => return Err(cursor.err("tables are not supported")),
+      Some(_) => {},
+    }
+
+    let (line, column) = (cursor.line, cursor.column);
+    let name = cursor.take_while(is_name_char);
+    if name.is_empty() {
+      return Err(cursor.err("expected a config name"))
+    }
+    cursor.skip_spaces();
+    if cursor.peek() != Some('=') {
+      return Err(cursor.err("expected '=' after the config name"))
+    }
+    cursor.next();
+    cursor.skip_spaces();
+
+    let value = match cursor.peek() {
+      Some('[') => {
+        cursor.next();
+        parse_array(&mut cursor)?
+      },
+      _ => parse_scalar(&mut cursor, false)?,
+    };
+
+    cursor.skip_spaces();
+    cursor.skip_comment();
+    match cursor.peek() {
+      None | Some('\n') => {},
+      Some(_) => return Err(cursor.err("unexpected characters after the value")),
+    }
+
+    entries.push(Entry { name, value, line, column });
+  }
+}
+
+/**
+ * Parses array elements after the opening '[', joining them in the list syntax understood by
+ * `Vec<T>` configs. Elements are escaped as needed, and arrays may nest.
+ */
+fn parse_array(cursor: &mut Cursor) -> Result<String, SyntaxErr> {
+  let mut elements = Vec::new();
+  loop {
+    cursor.skip_blank_lines();
+    match cursor.peek() {
+      None => return Err(cursor.err("unterminated array")),
+      Some(']') => {
+        cursor.next();
+        return Ok(::zcfg::list::join(&elements))
+      },
+      Some(_) => {},
+    }
+
+    let element = match cursor.peek() {
+      Some('[') => {
+        cursor.next();
+        parse_array(cursor)?
+      },
+      _ => parse_scalar(cursor, true)?,
+    };
+    elements.push(element);
+
+    cursor.skip_blank_lines();
+    match cursor.peek() {
+      Some(',') => { cursor.next(); },
+      Some(']') => {},
+      None => return Err(cursor.err("unterminated array")),
+      Some(_) => return Err(cursor.err("expected ',' or ']' in array")),
+    }
+  }
+}
+
+/**
+ * Parses a quoted string or a bare value.
+ *
+ * Bare values run to the end of the line or a comment, or within arrays to the next ',' or ']',
+ * and have surrounding whitespace trimmed.
+ */
+fn parse_scalar(cursor: &mut Cursor, in_array: bool) -> Result<String, SyntaxErr> {
+  match cursor.peek() {
+    Some('"') => {
+      cursor.next();
+      parse_basic_string(cursor)
+    },
+    Some('\'') => {
+      cursor.next();
+      let value = cursor.take_while(|c| c != '\'' && c != '\n');
+      if cursor.peek() != Some('\'') {
+        return Err(cursor.err("unterminated string"))
+      }
+      cursor.next();
+      Ok(value)
+    },
+    _ => {
+      let value = cursor.take_while(|c| {
+        c != '\n' && c != '#' && c != ';' && !(in_array && (c == ',' || c == ']'))
+      });
+      let value = value.trim();
+      if value.is_empty() {
+        Err(cursor.err("expected a value"))
+      } else {
+        Ok(value.to_owned())
+      }
+    },
+  }
+}
+
+/** Parses a double quoted string after its opening quote, resolving escapes. */
+fn parse_basic_string(cursor: &mut Cursor) -> Result<String, SyntaxErr> {
+  let mut value = String::new();
+  loop {
+    if let None | Some('\n') = cursor.peek() {
+      return Err(cursor.err("unterminated string"))
+    }
+    match cursor.next() {
+      None => return Err(cursor.err("unterminated string")),
+      Some('"') => return Ok(value),
+      Some('\\') => {
+        let escaped = match cursor.next() {
+          Some('"') => '"',
+          Some('\\') => '\\',
+          Some('n') => '\n',
+          Some('t') => '\t',
+          Some('r') => '\r',
+          _ => return Err(cursor.err("unknown escape sequence")),
+        };
+        value.push(escaped)
+      },
+      Some(c) => value.push(c),
+    }
+  }
+}
+
+fn is_name_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/** A character iterator that tracks its 1-based line and column. */
+struct Cursor<'a> {
+  chars: Peekable<Chars<'a>>,
+  line: usize,
+  column: usize,
+}
+
+impl <'a> Cursor<'a> {
+  fn new(contents: &'a str) -> Cursor<'a> {
+    Cursor {
+      chars: contents.chars().peekable(),
+      line: 1,
+      column: 1,
+    }
+  }
+
+  fn peek(&mut self) -> Option<char> {
+    self.chars.peek().cloned()
+  }
+
+  fn next(&mut self) -> Option<char> {
+    let c = self.chars.next();
+    if c == Some('\n') {
+      self.line += 1;
+      self.column = 1;
+    } else if c.is_some() {
+      self.column += 1;
+    }
+    c
+  }
+
+  fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> String {
+    let mut taken = String::new();
+    while let Some(c) = self.peek() {
+      if !f(c) {
+        break
+      }
+      taken.push(c);
+      self.next();
+    }
+    taken
+  }
+
+  fn skip_spaces(&mut self) {
+    self.take_while(|c| c != '\n' && c.is_whitespace());
+  }
+
+  fn skip_comment(&mut self) {
+    if self.peek() == Some('#') || self.peek() == Some(';') {
+      self.take_while(|c| c != '\n');
+    }
+  }
+
+  /** Skips whitespace, newlines and comments. */
+  fn skip_blank_lines(&mut self) {
+    loop {
+      self.skip_spaces();
+      self.skip_comment();
+      if self.peek() != Some('\n') {
+        return
+      }
+      self.next();
+    }
+  }
+
+  fn err(&self, message: &str) -> SyntaxErr {
+    SyntaxErr {
+      line: self.line,
+      column: self.column,
+      message: message.to_owned(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn entry(name: &str, value: &str, line: usize, column: usize) -> Entry {
+    Entry { name: name.to_owned(), value: value.to_owned(), line, column }
+  }
+
+  #[test]
+  fn parses_scalars_and_comments() {
+    let contents = "# leading comment\n\
+                    a = 1\n\
+                    \x20 b=Hello there ; trailing comment\n\
+                    \n\
+                    c = \"quoted # \\\"value\\\"\"\n\
+                    d = 'C:\\path'\n";
+
+    assert_eq!(parse_entries(contents), Ok(vec![
+      entry("a", "1", 2, 1),
+      entry("b", "Hello there", 3, 3),
+      entry("c", "quoted # \"value\"", 5, 1),
+      entry("d", "C:\\path", 6, 1),
+    ]));
+  }
+
+  #[test]
+  fn parses_multiline_arrays() {
+    let contents = "a = [1, \"2\",\n     '3', # comment\n]\nb = []";
+
+    assert_eq!(parse_entries(contents), Ok(vec![
+      entry("a", "1,2,3", 1, 1),
+      entry("b", "", 4, 1),
+    ]));
+  }
+
+  #[test]
+  fn array_elements_are_escaped() {
+    let contents = "a = [\"Hello, World\", 'C:\\path']\nb = [[1, 2], [3]]\nc = [\"\"]";
+
+    assert_eq!(parse_entries(contents), Ok(vec![
+      entry("a", "\"Hello, World\",\"C:\\\\path\"", 1, 1),
+      entry("b", "\"1,2\",3", 2, 1),
+      entry("c", "\"\"", 3, 1),
+    ]));
+  }
+
+  #[test]
+  fn reports_error_positions() {
+    assert_eq!(parse_entries("a = 1\nb 2"), Err(SyntaxErr {
+      line: 2,
+      column: 3,
+      message: "expected '=' after the config name".to_owned(),
+    }));
+    assert_eq!(parse_entries("a = \"open\n"), Err(SyntaxErr {
+      line: 1,
+      column: 10,
+      message: "unterminated string".to_owned(),
+    }));
+    assert_eq!(parse_entries("a = \"x\" y"), Err(SyntaxErr {
+      line: 1,
+      column: 9,
+      message: "unexpected characters after the value".to_owned(),
+    }));
+  }
+}
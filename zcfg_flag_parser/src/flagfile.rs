@@ -0,0 +1,184 @@
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use zcfg::ValueSource;
+use FlagInitErr;
+
+const FLAGFILE_PREFIX: &str = "--flagfile";
+
+/** The pseudo-path that reads a flagfile from stdin. */
+const STDIN_PATH: &str = "-";
+
+/** An argument after flagfile expansion. */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExpandedArg {
+  pub arg: String,
+  /** `CommandLine` if passed directly, or the flagfile line the argument was read from. */
+  pub source: ValueSource,
+  /** The index of the argument, or of the `--flagfile` that included it, in the original args. */
+  pub position: usize,
+}
+
+/**
+ * Replaces every `--flagfile=<path>` (or `--flagfile <path>`) argument with the flags in that
+ * file, in place, so that later arguments still override them.
+ *
+ * Flagfiles hold one argument per line. Blank lines and lines starting with `#` are skipped, and
+ * flagfiles may include other flagfiles. Arguments after `--` are never expanded.
+ */
+pub fn expand_flagfiles(args: Vec<String>) -> Result<Vec<ExpandedArg>, Vec<FlagInitErr>> {
+  expand_flagfiles_with(args, &read_flagfile)
+}
+
+/** `expand_flagfiles`, reading flagfile contents with `read`. */
+pub fn expand_flagfiles_with(
+    args: Vec<String>,
+    read: &dyn Fn(&Path) -> io::Result<String>)
+    -> Result<Vec<ExpandedArg>, Vec<FlagInitErr>> {
+  let mut expanded = Vec::new();
+  let mut errs = Vec::new();
+  let args = args.into_iter()
+    .enumerate()
+    .map(|(position, arg)| (arg, ValueSource::CommandLine, position))
+    .collect();
+  expand_into(args, read, &mut Vec::new(), &mut expanded, &mut errs);
+  if errs.is_empty() {
+    Ok(expanded)
+  } else {
+    Err(errs)
+  }
+}
+
+fn expand_into(
+    args: Vec<(String, ValueSource, usize)>,
+    read: &dyn Fn(&Path) -> io::Result<String>,
+    include_stack: &mut Vec<PathBuf>,
+    expanded: &mut Vec<ExpandedArg>,
+    errs: &mut Vec<FlagInitErr>) {
+  let mut args = args.into_iter();
+  while let Some((arg, source, position)) = args.next() {
+    let path = if arg == FLAGFILE_PREFIX {
+      match args.next() {
+        Some((path, _, _)) => PathBuf::from(path),
+        None => {
+          errs.push(FlagInitErr::MissingValue("flagfile".to_owned()));
+          continue
+        },
+      }
+    } else if arg.starts_with("--flagfile=") {
+      PathBuf::from(&arg[FLAGFILE_PREFIX.len() + 1..])
+    } else {
+      let is_terminator = arg == "--";
+      expanded.push(ExpandedArg { arg, source, position });
+      if is_terminator {
+        expanded.extend(args.by_ref().map(|(arg, source, position)| ExpandedArg { arg, source, position }));
+      }
+      continue
+    };
+
+    let identity = if path == Path::new(STDIN_PATH) {
+      path.clone()
+    } else {
+      fs::canonicalize(&path).unwrap_or_else(|_| path.clone())
+    };
+    if include_stack.contains(&identity) {
+      let mut cycle = include_stack.clone();
+      cycle.push(identity);
+      errs.push(FlagInitErr::FlagfileCycle(cycle));
+      continue
+    }
+
+    match read(&path) {
+      Err(e) => errs.push(FlagInitErr::FlagfileUnreadable(path, e.to_string())),
+      Ok(contents) => {
+        let file_args = contents.lines()
+          .enumerate()
+          .map(|(idx, line)| (idx + 1, line.trim()))
+          .filter(|&(_, line)| !line.is_empty() && !line.starts_with('#'))
+          .map(|(line_number, line)| (line.to_owned(), ValueSource::File(path.clone(), line_number), position))
+          .collect::<Vec<(String, ValueSource, usize)>>();
+        include_stack.push(identity);
+        expand_into(file_args, read, include_stack, expanded, errs);
+        include_stack.pop();
+      },
+    }
+  }
+}
+
+fn read_flagfile(path: &Path) -> io::Result<String> {
+  if path == Path::new(STDIN_PATH) {
+    let mut contents = String::new();
+    io::stdin().read_to_string(&mut contents)?;
+    Ok(contents)
+  } else {
+    fs::read_to_string(path)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::collections::HashMap;
+
+  fn strings(strs: &[&str]) -> Vec<String> {
+    strs.iter().map(|s| s.to_string()).collect()
+  }
+
+  fn expand(args: &[&str], files: &[(&str, &str)]) -> Result<Vec<String>, Vec<FlagInitErr>> {
+    expand_with_origin(args, files).map(|expanded| expanded.into_iter().map(|e| e.arg).collect())
+  }
+
+  fn expand_with_origin(args: &[&str], files: &[(&str, &str)]) -> Result<Vec<ExpandedArg>, Vec<FlagInitErr>> {
+    let files = files.iter()
+      .map(|&(path, contents)| (PathBuf::from(path), contents.to_owned()))
+      .collect::<HashMap<PathBuf, String>>();
+    let read = |path: &Path| {
+      files.get(path).cloned().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found"))
+    };
+    expand_flagfiles_with(strings(args), &read)
+  }
+
+  #[test]
+  fn flagfiles_are_spliced_in_place() {
+    let files = [
+      ("a.flags", "# comment\n--x=1\n\n  --flagfile=b.flags\n--y=2\n"),
+      ("b.flags", "--z=3"),
+    ];
+
+    assert_eq!(expand(&["--w=0", "--flagfile=a.flags", "--x=4", "--", "--flagfile=a.flags"], &files),
+               Ok(strings(&["--w=0", "--x=1", "--z=3", "--y=2", "--x=4", "--", "--flagfile=a.flags"])));
+    assert_eq!(expand(&["--flagfile", "b.flags"], &files), Ok(strings(&["--z=3"])));
+  }
+
+  #[test]
+  fn flagfile_args_record_their_line() {
+    assert_eq!(expand_with_origin(&["--flagfile=a.flags", "--y=2"], &[("a.flags", "# x\n\n--x=1")]), Ok(vec![
+      ExpandedArg { arg: "--x=1".to_owned(), source: ValueSource::File(PathBuf::from("a.flags"), 3), position: 0 },
+      ExpandedArg { arg: "--y=2".to_owned(), source: ValueSource::CommandLine, position: 1 },
+    ]));
+  }
+
+  #[test]
+  fn stdin_flagfiles_are_read() {
+    assert_eq!(expand(&["--flagfile=-"], &[("-", "--x=1")]), Ok(strings(&["--x=1"])));
+  }
+
+  #[test]
+  fn cycles_are_detected() {
+    let files = [
+      ("a.flags", "--flagfile=b.flags"),
+      ("b.flags", "--flagfile=a.flags"),
+    ];
+
+    assert_eq!(expand(&["--flagfile=a.flags"], &files), Err(vec![FlagInitErr::FlagfileCycle(
+      vec![PathBuf::from("a.flags"), PathBuf::from("b.flags"), PathBuf::from("a.flags")])]));
+  }
+
+  #[test]
+  fn unreadable_flagfiles_are_reported() {
+    assert_eq!(expand(&["--flagfile=missing.flags"], &[]), Err(vec![
+      FlagInitErr::FlagfileUnreadable(PathBuf::from("missing.flags"), "not found".to_owned())]));
+  }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use terminal_size::terminal_size;
+use terminal_size::Width;
+use zcfg::ConfigMetadata;
+
+/** The width used when the output is not attached to a terminal. */
+const DEFAULT_WIDTH: usize = 80;
+
+/** How far config descriptions are indented beneath their flag. */
+const DESCRIPTION_INDENT: usize = 6;
+
+/**
+ * Renders `--help` text for a set of configs.
+ *
+ * Each config is listed with its type, default value, description and defining location. Configs
+ * are grouped by the file that defined them, and each description is wrapped to the width of the
+ * terminal.
+ */
+pub struct HelpRenderer {
+  width: usize,
+}
+
+impl Default for HelpRenderer {
+  fn default() -> HelpRenderer {
+    HelpRenderer::new()
+  }
+}
+
+impl HelpRenderer {
+  /** Constructs a renderer that wraps text to the current terminal width. */
+  pub fn new() -> HelpRenderer {
+    let width = terminal_size()
+      .map(|(Width(w), _)| w as usize)
+      .unwrap_or(DEFAULT_WIDTH);
+    HelpRenderer::with_width(width)
+  }
+
+  /** Constructs a renderer that wraps text to a fixed width. */
+  pub fn with_width(width: usize) -> HelpRenderer {
+    HelpRenderer {
+      width,
+    }
+  }
+
+  /** Renders help for every config registered in `zcfg::STATIC_CONFIG_INITIALIZERS`. */
+  pub fn render_static_configs(&self) -> String {
+    let metadata = zcfg::STATIC_CONFIG_INITIALIZERS.read()
+      .expect("initializers were poisoned")
+      .iter()
+      .map(|i| i.metadata().clone())
+      .collect::<Vec<ConfigMetadata>>();
+    self.render(&metadata)
+  }
+
+  /** Renders help for the provided configs. */
+  pub fn render(&self, metadata: &[ConfigMetadata]) -> String {
+    let mut metadata_by_file: BTreeMap<&str, Vec<&ConfigMetadata>> = BTreeMap::new();
+    for m in metadata.iter() {
+      metadata_by_file.entry(m.file()).or_default().push(m);
+    }
+
+    let mut out = String::new();
+    out.push_str("Flags:\n");
+    for (file, mut file_metadata) in metadata_by_file.into_iter() {
+      file_metadata.sort_by(|a, b| a.config_name().cmp(b.config_name()));
+      out.push_str(&format!("\n  {}:\n", file));
+      for m in file_metadata.into_iter() {
+        out.push_str(&format!("    --{}: {}", m.config_name(), short_type_name(m.type_name())));
+        match m.default_value() {
+          Some("") => out.push_str(" = \"\""),
+          Some(default_value) => out.push_str(&format!(" = {}", default_value)),
+          None => {},
+        }
+        out.push_str(&format!("  ({}:{})\n", m.file(), m.line()));
+        for line in wrap(m.description(), self.width.saturating_sub(DESCRIPTION_INDENT)) {
+          out.push_str(&format!("{:indent$}{}\n", "", line, indent = DESCRIPTION_INDENT));
+        }
+      }
+    }
+    out
+  }
+}
+
+/**
+ * Strips module paths from a `std::any::type_name`, so `alloc::vec::Vec<alloc::string::String>`
+ * becomes `Vec<String>`.
+ */
+fn short_type_name(type_name: &str) -> String {
+  let mut out = String::new();
+  let mut segment_start = 0;
+  let mut chars = type_name.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c == ':' && chars.peek() == Some(&':') {
+      chars.next();
+      out.truncate(segment_start);
+    } else {
+      if !(c.is_alphanumeric() || c == '_') {
+        segment_start = out.len() + c.len_utf8();
+      }
+      out.push(c);
+    }
+  }
+  out
+}
+
+/**
+ * Greedily wraps text on whitespace so that no line exceeds `width`.
+ *
+ * Words longer than `width` are left intact on their own line.
+ */
+fn wrap(text: &str, width: usize) -> Vec<String> {
+  let mut lines = Vec::new();
+  let mut current = String::new();
+  for word in text.split_whitespace() {
+    if !current.is_empty() && current.len() + 1 + word.len() > width {
+      lines.push(current);
+      current = String::new();
+    }
+    if !current.is_empty() {
+      current.push(' ');
+    }
+    current.push_str(word);
+  }
+  if !current.is_empty() {
+    lines.push(current);
+  }
+  lines
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn wrap_respects_width() {
+    assert_eq!(wrap("the quick brown fox jumps", 10),
+               vec!["the quick".to_owned(), "brown fox".to_owned(), "jumps".to_owned()]);
+  }
+
+  #[test]
+  fn wrap_keeps_long_words_whole() {
+    assert_eq!(wrap("a net_protocol_timeout_ms b", 5),
+               vec!["a".to_owned(), "net_protocol_timeout_ms".to_owned(), "b".to_owned()]);
+  }
+
+  #[test]
+  fn short_type_name_strips_paths() {
+    assert_eq!(short_type_name("u32"), "u32");
+    assert_eq!(short_type_name("core::option::Option<alloc::vec::Vec<alloc::string::String>>"),
+               "Option<Vec<String>>");
+  }
+
+  #[test]
+  fn render_groups_by_file() {
+    let metadata = vec![
+      ConfigMetadata::new("zeta".to_owned(), "src/a.rs".to_owned(), 3, "Last in a.",
+                          "u32", Some("20000".to_owned()), false),
+      ConfigMetadata::new("beta".to_owned(), "src/b.rs".to_owned(), 1, "Only in b.",
+                          "alloc::string::String", Some("".to_owned()), false),
+      ConfigMetadata::new("alpha".to_owned(), "src/a.rs".to_owned(), 7, "First in a.",
+                          "my_crate::BuildStrategy", None, false),
+    ];
+
+    let rendered = HelpRenderer::with_width(80).render(&metadata);
+
+    assert_eq!(rendered, "Flags:\n\
+                          \n  src/a.rs:\n\
+                          \x20   --alpha: BuildStrategy  (src/a.rs:7)\n\
+                          \x20     First in a.\n\
+                          \x20   --zeta: u32 = 20000  (src/a.rs:3)\n\
+                          \x20     Last in a.\n\
+                          \n  src/b.rs:\n\
+                          \x20   --beta: String = \"\"  (src/b.rs:1)\n\
+                          \x20     Only in b.\n");
+  }
+}
@@ -1,25 +1,245 @@
+#[cfg(test)]
+#[macro_use]
+extern crate lazy_static;
+#[cfg_attr(test, macro_use)]
 extern crate zcfg;
 extern crate itertools;
+extern crate terminal_size;
+
+mod flagfile;
+mod help;
+mod suggest;
+
+pub use help::HelpRenderer;
 
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
 use itertools::Itertools;
+use suggest::suggest_configs;
+use zcfg::Candidate;
 use zcfg::ConfigMetadata;
 use zcfg::InitErr;
+use zcfg::RepeatPolicy;
+use zcfg::ValueSource;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum FlagInitErr {
-  UndefinedArg(String),
-  InitErr(InitErr),
+  /** A flag that matches no config, with the closest config names as suggestions. */
+  UndefinedArg {
+    name: String,
+    suggestions: Vec<ConfigMetadata>,
+  },
+  /** A flag that is not a switch was given without a value. */
+  MissingValue(String),
+  /** More than one linked config shares a name, so flags could not be routed to them. */
+  DuplicateConfig {
+    name: String,
+    locations: Vec<ConfigMetadata>,
+  },
+  /** A flag was repeated for a config with `zcfg::RepeatPolicy::Error`. */
+  RepeatedFlag(String),
+  /** A value that does not belong to any flag, under `PositionalPolicy::Reject`. */
+  OrphanValue(String),
+  /** A `--flagfile` could not be read. */
+  FlagfileUnreadable(PathBuf, String),
+  /** A `--flagfile` includes itself, through the listed chain of flagfiles. */
+  FlagfileCycle(Vec<PathBuf>),
+  /** A config's default value fails its validators. See `zcfg::invalid_defaults`. */
+  InvalidDefault(InitErr),
+  /** A flag's value was rejected by its config. */
+  InitErr {
+    flag: String,
+    /** The index of the flag in the parsed args, or of the `--flagfile` that included it. */
+    position: usize,
+    err: InitErr,
+  },
+}
+
+impl fmt::Display for FlagInitErr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      FlagInitErr::UndefinedArg { ref name, ref suggestions } => {
+        write!(f, "unknown flag --{}", name)?;
+        for suggestion in suggestions.iter() {
+          write!(f, "\n  did you mean --{}? {}", suggestion.config_name(), suggestion.description())?;
+        }
+        Ok(())
+      },
+      FlagInitErr::MissingValue(ref name) => write!(f, "flag --{} requires a value", name),
+      FlagInitErr::DuplicateConfig { ref name, ref locations } => {
+        write!(f, "config `{}` is defined more than once:", name)?;
+        for location in locations.iter() {
+          write!(f, "\n  {}:{}", location.file(), location.line())?;
+        }
+        Ok(())
+      },
+      FlagInitErr::RepeatedFlag(ref name) => write!(f, "flag --{} was given more than once", name),
+      FlagInitErr::OrphanValue(ref value) => {
+        write!(f, "argument `{}` does not belong to any flag", value)
+      },
+      FlagInitErr::FlagfileUnreadable(ref path, ref e) => {
+        write!(f, "could not read flagfile {}: {}", path.display(), e)
+      },
+      FlagInitErr::FlagfileCycle(ref chain) => {
+        let chain = chain.iter().map(|p| p.display().to_string()).collect::<Vec<String>>();
+        write!(f, "flagfile includes itself: {}", chain.join(" -> "))
+      },
+      FlagInitErr::InvalidDefault(ref err) => write!(f, "invalid default: {}", err),
+      FlagInitErr::InitErr { ref flag, position, ref err } => {
+        write!(f, "argument {} (--{}): {}", position, flag, err)
+      },
+    }
+  }
+}
+
+impl Error for FlagInitErr {}
+
+/** The result of a successful flag parse. */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParseOutcome {
+  /** Every flag was applied to its config. The remaining arguments are returned. */
+  Parsed(ParsedArgs),
+  /** `--help` was passed, so help was printed and no configs were set. */
+  HelpRequested,
+}
+
+/** The arguments left over after all flags have been applied. */
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ParsedArgs {
+  positional: Vec<String>,
+  after_terminator: Vec<String>,
+}
+
+impl ParsedArgs {
+  /** Arguments that were not flags or flag values, in order, excluding anything after `--`. */
+  pub fn positional(&self) -> &[String] {
+    &self.positional
+  }
+
+  /** Every argument after the first `--`, in order. */
+  pub fn after_terminator(&self) -> &[String] {
+    &self.after_terminator
+  }
 }
 
-pub struct FlagParser;
+/** How `FlagParser` treats arguments that are not flags. */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PositionalPolicy {
+  /** Flags and positional arguments may be freely mixed. */
+  Interleave,
+  /**
+   * The first positional argument ends flag parsing; it and everything after are positional, up to
+   * a `--`.
+   */
+  StopAtFirst,
+  /** Positional arguments are not allowed, and are reported as `FlagInitErr::OrphanValue`. */
+  Reject,
+}
+
+/** The outcome of collecting flags, their candidates with argument positions, and flag errors. */
+type Collected = (ParseOutcome, Vec<(Candidate, usize)>, Vec<FlagInitErr>);
+
+pub struct FlagParser {
+  positional_policy: PositionalPolicy,
+}
+
+impl Default for FlagParser {
+  fn default() -> FlagParser {
+    FlagParser::new()
+  }
+}
 
 impl FlagParser {
   pub fn new() -> FlagParser {
-    FlagParser
+    FlagParser {
+      positional_policy: PositionalPolicy::Interleave,
+    }
+  }
+
+  /** Sets how positional arguments are handled. Defaults to `PositionalPolicy::Interleave`. */
+  pub fn positional_policy(mut self, positional_policy: PositionalPolicy) -> FlagParser {
+    self.positional_policy = positional_policy;
+    self
+  }
+
+  /**
+   * Applies every `--name=value` or `--name value` argument to its matching config.
+   *
+   * Switch configs (see `zcfg::ConfigParseable::is_switch`) are set to "true" by a bare `--name`,
+   * and to "false" by `--noname` or `--no-name`. A bare switch never consumes the next argument.
+   *
+   * Arguments that are not flags or flag values are returned as positional arguments, subject to
+   * the parser's `PositionalPolicy`. Everything after `--` is returned untouched.
+   *
+   * A flag may be repeated for configs that accumulate values (see
+   * `zcfg::ConfigParseable::accumulates`), like `--include=a --include=b`, and its values are
+   * combined. For other configs, the config's `zcfg::RepeatPolicy` decides whether the last
+   * occurrence wins or the repetition is an error.
+   *
+   * `--flagfile=<path>` is replaced by the arguments in that file, one per line, so that later
+   * arguments override it. Lines starting with `#` are comments, flagfiles may include other
+   * flagfiles, and `--flagfile=-` reads from stdin.
+   *
+   * If `--help` is present, help for all configs is printed to stdout instead, and
+   * `ParseOutcome::HelpRequested` is returned without setting any config.
+   */
+  pub fn parse_from_args<I: Iterator<Item = String>>(&self, args: I) -> Result<ParseOutcome, Vec<FlagInitErr>> {
+    let (outcome, candidates, mut set_errs) = self.collect(args)?;
+    if outcome == ParseOutcome::HelpRequested {
+      return Ok(outcome)
+    }
+
+    let tagged = candidates.into_iter()
+      .map(|(candidate, position)| {
+        let flag = candidate.config_name().to_owned();
+        (candidate, (flag, position))
+      });
+    match zcfg::apply_candidates(tagged) {
+      Ok(apply_errs) => set_errs.extend(apply_errs.into_iter()
+        .map(|((flag, position), err)| FlagInitErr::InitErr { flag, position, err })),
+      Err(invalid_defaults) => return Err(invalid_defaults.into_iter().map(FlagInitErr::InvalidDefault).collect()),
+    }
+
+    if set_errs.is_empty() {
+      Ok(outcome)
+    } else {
+      Err(set_errs)
+    }
   }
 
-  pub fn parse_from_args<I: Iterator<Item = String>>(&self, args: I) -> Result<(), Vec<FlagInitErr>> {
+  /**
+   * Finds the config matching each flag, as `parse_from_args` does, without applying them.
+   *
+   * The candidates can be given to a `zcfg::ConfigLoader` to combine flags with other sources.
+   * `--help` is handled just as in `parse_from_args`, and yields no candidates.
+   */
+  pub fn collect_from_args<I: Iterator<Item = String>>(&self, args: I) -> Result<(ParseOutcome, Vec<Candidate>), Vec<FlagInitErr>> {
+    let (outcome, candidates, errs) = self.collect(args)?;
+    if errs.is_empty() {
+      Ok((outcome, candidates.into_iter().map(|(candidate, _)| candidate).collect()))
+    } else {
+      Err(errs)
+    }
+  }
+
+  /**
+   * Collects candidates for every flag, with the position of its argument, along with any errors
+   * for individual flags.
+   *
+   * Errors that prevent collecting anything at all are returned as `Err`.
+   */
+  fn collect<I: Iterator<Item = String>>(&self, args: I) -> Result<Collected, Vec<FlagInitErr>> {
+    let args = args.collect::<Vec<String>>();
+    // `--help` on the command line works even if a flagfile can't be read.
+    let help_on_command_line = requests_help(args.iter().map(|a| a.as_str()));
+    let args = if help_on_command_line { Vec::new() } else { flagfile::expand_flagfiles(args)? };
+    if help_on_command_line || requests_help(args.iter().map(|a| a.arg.as_str())) {
+      print!("{}", HelpRenderer::new().render_static_configs());
+      return Ok((ParseOutcome::HelpRequested, Vec::new(), Vec::new()))
+    }
+
     let initializers = zcfg::STATIC_CONFIG_INITIALIZERS.read()
       .expect("initializers were poisoned");
 
@@ -29,114 +249,178 @@ impl FlagParser {
         .collect::<Vec<ConfigMetadata>>();
     initializer_meta_sorted.sort_by(|a, b| a.config_name().cmp(b.config_name()));
 
-    let flag_name_conflicts: Vec<(String, Vec<ConfigMetadata>)> =
-      initializer_meta_sorted.into_iter()
+    let flag_name_conflicts: Vec<FlagInitErr> =
+      initializer_meta_sorted.iter()
         .group_by(|i| i.config_name().to_owned())
         .into_iter()
-        .map(|(key, metadata_objects)| (key, metadata_objects.collect::<Vec<ConfigMetadata>>()))
-        .filter(|&(_, ref metadata_objects)| metadata_objects.len() > 1)
+        .map(|(key, metadata_objects)| (key, metadata_objects.cloned().collect::<Vec<ConfigMetadata>>()))
+        .filter(|(_, metadata_objects)| metadata_objects.len() > 1)
+        .map(|(name, locations)| FlagInitErr::DuplicateConfig { name, locations })
         .collect();
 
-    // TODO: Something more user friendly
-    assert_eq!(flag_name_conflicts, Vec::new());
-
-    let arg_elements = args.map(|content| {
-      if content == "--".to_owned() {
-        ArgComponent::Terminator
-      } else if content.starts_with("--") {
-        // TODO: This isn't perfect -- more robust parsing later
-        if content.contains('=') {
-          ArgComponent::CompleteArg(content)
-        } else {
-          ArgComponent::ArgPrefix(content)
-        }
-      } else {
-        ArgComponent::ArgSuffix(content)
-      }
-    })
-    .peekable()
-    .batching(|mut it| {
-      let first = it.next();
-      if first.is_none() { return None }
-      match first {
-        None => None,
-        Some(ArgComponent::Terminator) => None,
-        Some(ArgComponent::ArgSuffix(value)) => {
-          Some(Err(format!("Arg element [{}] did not have a corresponding key", value)))
+    if !flag_name_conflicts.is_empty() {
+      return Err(flag_name_conflicts)
+    }
+
+    let mut config_name_to_idx = HashMap::new();
+    for (idx, e) in initializers.iter().enumerate() {
+      config_name_to_idx.insert(e.config_name(), idx);
+    }
+
+    let mut components = args.into_iter()
+      .map(|a| (ArgComponent::from_arg(a.arg), a.source, a.position))
+      .peekable();
+    let mut captures = Vec::new();
+    let mut parsed_args = ParsedArgs::default();
+    let mut set_errs = Vec::new();
+    while let Some((component, source, position)) = components.next() {
+      match component {
+        ArgComponent::Terminator => {
+          parsed_args.after_terminator.extend(components.by_ref().map(|(c, _, _)| c.into_arg()));
+        },
+        ArgComponent::ArgSuffix(value) => {
+          match self.positional_policy {
+            PositionalPolicy::Interleave => parsed_args.positional.push(value),
+            PositionalPolicy::StopAtFirst => {
+              parsed_args.positional.push(value);
+              // A later `--` is left for the loop, to split off the tail as usual.
+              while components.peek().is_some_and(|(c, _, _)| !matches!(c, ArgComponent::Terminator)) {
+                let (c, _, _) = components.next().unwrap();
+                parsed_args.positional.push(c.into_arg());
+              }
+            },
+            PositionalPolicy::Reject => set_errs.push(FlagInitErr::OrphanValue(value)),
+          }
         },
-        Some(ArgComponent::CompleteArg(name_and_value)) =>  {
+        ArgComponent::CompleteArg(name_and_value) => {
           let eq_byte_idx = name_and_value.find('=').unwrap();
           let (name, value_plus_eq) = name_and_value.split_at(eq_byte_idx);
-          Some(Ok(ArgCapture {
+          captures.push(ArgCapture {
             label: name.chars().skip(2 /* -- */).collect::<String>(),
             value: Some(value_plus_eq.chars().skip(1 /* = */).collect::<String>()),
-          }))
-        }
-        Some(ArgComponent::ArgPrefix(name)) => {
-          let mut value_opt = None;
-          if let Some(&ArgComponent::ArgSuffix(ref s)) = it.peek() {
-            // TODO: Fix janky clone
-            value_opt = Some(s.clone())
-          }
-          match value_opt {
-            Some(value) => {
-              // Toss next element
-              it.next();
-              Some(Ok(ArgCapture {
-                label: name.chars().skip(2 /* == */).collect::<String>(),
-                value: Some(value.to_owned()),
-              }))
-            },
-            None => {
-              Some(Ok(ArgCapture {
-                label: name.chars().skip(2 /* == */).collect::<String>(),
-                value: None,
-              }))
-            }
+            source,
+            position,
+          })
+        },
+        ArgComponent::ArgPrefix(name) => {
+          let label = name.chars().skip(2 /* -- */).collect::<String>();
+          if let Some(mut capture) = capture_switch(&label, &initializers, &config_name_to_idx) {
+            capture.source = source;
+            capture.position = position;
+            captures.push(capture);
+            continue
           }
-        }
-      }
-    })
-    .collect::<Vec<Result<ArgCapture, String>>>();
-
 
-    let mut captures = Vec::new();
-    for arg in arg_elements.into_iter() {
-      if let Err(e) = arg {
-        panic!(e)
+          let value = match components.peek() {
+            Some(&(ArgComponent::ArgSuffix(_), _, _)) => components.next().map(|(c, _, _)| c.into_arg()),
+            _ => None,
+          };
+          captures.push(ArgCapture {
+            label,
+            value,
+            source,
+            position,
+          })
+        },
       }
-      captures.push(arg.unwrap());
     }
 
+    let initializer = |label: &str| config_name_to_idx.get(label).map(|idx| &initializers[*idx]);
+    let accumulates = |label: &str| initializer(label).map(|i| i.accumulates()).unwrap_or(false);
 
-    let mut config_name_to_idx = HashMap::new();
-    for (idx, e) in initializers.iter().enumerate() {
-      config_name_to_idx.insert(e.config_name().clone(), idx);
-    }
+    // Flagfiles are spliced in where they appear, so a flag is overridden by any later occurrence
+    // of the same flag when either of the two came from a flagfile. Every flag for a
+    // `RepeatPolicy::LastWins` config is overridden by a later one. Accumulating configs keep every
+    // occurrence.
+    let overridden = captures.iter().enumerate()
+      .map(|(idx, capture)| {
+        let last_wins = initializer(&capture.label)
+          .map(|i| i.repeat_policy() == RepeatPolicy::LastWins)
+          .unwrap_or(false);
+        !accumulates(&capture.label)
+          && captures[idx + 1..].iter().any(|later| {
+            later.label == capture.label
+              && (last_wins || capture.is_from_flagfile() || later.is_from_flagfile())
+          })
+      })
+      .collect::<Vec<bool>>();
 
-    let mut set_errs = Vec::new();
-    for capture in captures.into_iter() {
-      let label: &str = &capture.label;
-      if !config_name_to_idx.contains_key(label) {
-        set_errs.push(FlagInitErr::UndefinedArg(capture.label.clone()))
+    let mut candidates: Vec<(Candidate, usize)> = Vec::new();
+    let mut candidate_idx: HashMap<String, usize> = HashMap::new();
+    for (capture, overridden) in captures.into_iter().zip(overridden) {
+      if overridden {
+        continue
+      }
+      if !config_name_to_idx.contains_key(capture.label.as_str()) {
+        set_errs.push(FlagInitErr::UndefinedArg {
+          suggestions: suggest_configs(&capture.label, &initializer_meta_sorted),
+          name: capture.label,
+        })
       } else {
-        let config_idx = config_name_to_idx.get(label).unwrap();
-        let initializer_ref = initializers.get(*config_idx).unwrap();
-        let result = match capture.value {
-          None => initializer_ref.set_statically("True"),
-          Some(ref v) => initializer_ref.set_statically(v)
-        };
-
-        if result.is_err() {
-          set_errs.push(FlagInitErr::InitErr(result.err().unwrap()))
+        match (capture.value, candidate_idx.get(&capture.label)) {
+          (None, _) => set_errs.push(FlagInitErr::MissingValue(capture.label)),
+          // Repeated flags for accumulating configs are combined into one candidate, which takes
+          // the source of the last occurrence.
+          (Some(value), Some(&idx)) if accumulates(&capture.label) => {
+            let combined = zcfg::list::concat(&[candidates[idx].0.value(), value.as_str()]);
+            candidates[idx] = (Candidate::new(capture.label, combined, capture.source), capture.position);
+          },
+          (Some(_), Some(_)) => set_errs.push(FlagInitErr::RepeatedFlag(capture.label)),
+          (Some(value), None) => {
+            candidate_idx.insert(capture.label.clone(), candidates.len());
+            candidates.push((Candidate::new(capture.label, value, capture.source), capture.position))
+          },
         }
       }
     }
-    if set_errs.is_empty() {
-      Ok(())
-    } else {
-      Err(set_errs)
-    }
+    Ok((ParseOutcome::Parsed(parsed_args), candidates, set_errs))
+  }
+}
+
+/** Whether `--help` appears before any `--`. */
+fn requests_help<'a, I: Iterator<Item = &'a str>>(args: I) -> bool {
+  args.take_while(|a| *a != "--").any(|a| a == "--help")
+}
+
+/**
+ * Captures a bare `--label` if it names a switch config, or negates one as `--nolabel` or
+ * `--no-label`.
+ *
+ * An exact config name match always takes precedence over a negation.
+ */
+fn capture_switch(
+    label: &str,
+    initializers: &[zcfg::ConfigInitializer],
+    config_name_to_idx: &HashMap<&str, usize>)
+    -> Option<ArgCapture> {
+  let is_switch = |name: &str| {
+    config_name_to_idx.get(name).map(|idx| initializers[*idx].is_switch())
+  };
+
+  match is_switch(label) {
+    Some(true) => {
+      return Some(ArgCapture {
+        label: label.to_owned(),
+        value: Some("true".to_owned()),
+        source: ValueSource::CommandLine,
+        position: 0,
+      })
+    },
+    Some(false) => return None,
+    None => {},
+  }
+
+  let negated = label.strip_prefix("no-").or_else(|| label.strip_prefix("no"));
+  match negated {
+    Some(name) if is_switch(name) == Some(true) => {
+      Some(ArgCapture {
+        label: name.to_owned(),
+        value: Some("false".to_owned()),
+        source: ValueSource::CommandLine,
+        position: 0,
+      })
+    },
+    _ => None,
   }
 }
 
@@ -148,8 +432,251 @@ enum ArgComponent {
   Terminator,
 }
 
+impl ArgComponent {
+  fn from_arg(content: String) -> ArgComponent {
+    if content == "--" {
+      ArgComponent::Terminator
+    } else if content.starts_with("--") {
+      // TODO: This isn't perfect -- more robust parsing later
+      if content.contains('=') {
+        ArgComponent::CompleteArg(content)
+      } else {
+        ArgComponent::ArgPrefix(content)
+      }
+    } else {
+      ArgComponent::ArgSuffix(content)
+    }
+  }
+
+  fn into_arg(self) -> String {
+    match self {
+      ArgComponent::CompleteArg(content) => content,
+      ArgComponent::ArgPrefix(content) => content,
+      ArgComponent::ArgSuffix(content) => content,
+      ArgComponent::Terminator => "--".to_owned(),
+    }
+  }
+}
+
 #[derive(Clone,Debug, PartialEq, Eq)]
 struct ArgCapture {
   pub label: String,
-  pub value: Option<String>
+  pub value: Option<String>,
+  pub source: ValueSource,
+  pub position: usize,
+}
+
+impl ArgCapture {
+  fn is_from_flagfile(&self) -> bool {
+    self.source != ValueSource::CommandLine
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  define_cfg!(switch_1, bool, false, "some switch_1 configuration");
+  define_cfg!(switch_2, bool, true, "some switch_2 configuration");
+  define_cfg!(switch_3, bool, true, "some switch_3 configuration");
+  define_cfg!(after_switch_1, String, "".to_owned(), "some after_switch_1 configuration");
+  define_cfg!(valued_1, u32, 0u32, "some valued_1 configuration");
+  define_cfg!(switch_4, bool, false, "some switch_4 configuration");
+  define_cfg!(switch_5, bool, false, "some switch_5 configuration");
+  define_cfg!(positional_1, String, "".to_owned(), "some positional_1 configuration");
+  define_cfg!(positional_2, String, "".to_owned(), "some positional_2 configuration");
+  define_cfg!(flagfile_1, String, "".to_owned(), "some flagfile_1 configuration");
+  define_cfg!(flagfile_2, String, "".to_owned(), "some flagfile_2 configuration");
+  define_cfg!(flagfile_3, String, "".to_owned(), "some flagfile_3 configuration");
+  define_cfg!(weights_1, ::std::collections::BTreeMap<String, u32>, ::std::collections::BTreeMap::new(),
+              "some weights_1 configuration");
+  define_cfg!(tags_1, Vec<String>, Vec::new(), "some tags_1 configuration");
+  define_cfg!(optional_tags_1, Option<Vec<String>>, None, "some optional_tags_1 configuration");
+  define_cfg!(optional_switch_1, Option<bool>, None, "some optional_switch_1 configuration");
+  define_cfg!(last_wins_1, u32, 0u32, "some last_wins_1 configuration", repeated: LastWins);
+  define_cfg!(repeated_1, u32, 0u32, "some repeated_1 configuration");
+  define_cfg!(invalid_1, Vec<u32>, Vec::new(), "some invalid_1 configuration");
+
+  fn args(args: &[&str]) -> ::std::vec::IntoIter<String> {
+    args.iter().map(|a| a.to_string()).collect::<Vec<String>>().into_iter()
+  }
+
+  #[test]
+  fn bare_switches_are_set_and_negated() {
+    let outcome = FlagParser::new().parse_from_args(
+      args(&["--switch_1", "--after_switch_1=x", "--noswitch_2", "--no-switch_3"]));
+
+    assert_eq!(outcome, Ok(ParseOutcome::Parsed(ParsedArgs::default())));
+    assert!(switch_1::CONFIG.get_value());
+    assert!(!switch_2::CONFIG.get_value());
+    assert!(!switch_3::CONFIG.get_value());
+    assert_eq!(after_switch_1::CONFIG.get_value(), "x".to_owned());
+  }
+
+  #[test]
+  fn bare_valued_flags_are_missing_a_value() {
+    let outcome = FlagParser::new().parse_from_args(args(&["--valued_1"]));
+
+    assert_eq!(outcome, Err(vec![FlagInitErr::MissingValue("valued_1".to_owned())]));
+  }
+
+  #[test]
+  fn positional_args_are_returned_in_order() {
+    let outcome = FlagParser::new().parse_from_args(
+      args(&["a", "--switch_4", "b", "--positional_1", "c", "d", "--", "--switch_4", "e"]));
+
+    assert_eq!(outcome, Ok(ParseOutcome::Parsed(ParsedArgs {
+      positional: vec!["a".to_owned(), "b".to_owned(), "d".to_owned()],
+      after_terminator: vec!["--switch_4".to_owned(), "e".to_owned()],
+    })));
+    assert!(switch_4::CONFIG.get_value());
+    assert_eq!(positional_1::CONFIG.get_value(), "c".to_owned());
+  }
+
+  #[test]
+  fn stop_at_first_positional_leaves_later_flags_alone() {
+    let outcome = FlagParser::new()
+      .positional_policy(PositionalPolicy::StopAtFirst)
+      .parse_from_args(args(&["--switch_5", "a", "--positional_2=b", "--", "c", "--"]));
+
+    assert_eq!(outcome, Ok(ParseOutcome::Parsed(ParsedArgs {
+      positional: vec!["a".to_owned(), "--positional_2=b".to_owned()],
+      after_terminator: vec!["c".to_owned(), "--".to_owned()],
+    })));
+    assert!(switch_5::CONFIG.get_value());
+    assert_eq!(positional_2::CONFIG.get_value(), "".to_owned());
+  }
+
+  #[test]
+  fn rejected_positional_args_are_orphans() {
+    let outcome = FlagParser::new()
+      .positional_policy(PositionalPolicy::Reject)
+      .parse_from_args(args(&["a", "--", "b"]));
+
+    assert_eq!(outcome, Err(vec![FlagInitErr::OrphanValue("a".to_owned())]));
+  }
+
+  #[test]
+  fn duplicate_config_lists_every_location() {
+    let err = FlagInitErr::DuplicateConfig {
+      name: "dup".to_owned(),
+      locations: vec![
+        ConfigMetadata::new("dup".to_owned(), "src/a.rs".to_owned(), 3, "", "u32", None, false),
+        ConfigMetadata::new("dup".to_owned(), "src/b.rs".to_owned(), 9, "", "u32", None, false),
+      ],
+    };
+
+    assert_eq!(err.to_string(), "config `dup` is defined more than once:\n  src/a.rs:3\n  src/b.rs:9");
+  }
+
+  #[test]
+  fn undefined_args_suggest_close_names() {
+    let outcome = FlagParser::new().parse_from_args(args(&["--positonal_1=a"]));
+
+    match outcome {
+      Err(ref errs) => match errs.as_slice() {
+        [FlagInitErr::UndefinedArg { name, suggestions }] => {
+          assert_eq!(name, "positonal_1");
+          assert_eq!(suggestions.iter().map(|m| m.config_name()).collect::<Vec<&str>>(),
+                     vec!["positional_1", "positional_2"]);
+        },
+        _ => panic!("unexpected errors {:?}", errs),
+      },
+      Ok(_) => panic!("unexpected success"),
+    }
+  }
+
+  #[test]
+  fn help_is_printed_before_flagfiles_are_read() {
+    let outcome = FlagParser::new().parse_from_args(args(&["--help", "--flagfile=/nonexistent/zcfg.flags"]));
+
+    assert_eq!(outcome, Ok(ParseOutcome::HelpRequested));
+  }
+
+  #[test]
+  fn later_flags_override_flagfiles() {
+    let path = ::std::env::temp_dir().join(format!("zcfg_flagfile_{}.flags", ::std::process::id()));
+    ::std::fs::write(&path, "--flagfile_1=from_file\n--flagfile_2=from_file\n").unwrap();
+
+    let outcome = FlagParser::new().parse_from_args(
+      args(&[&format!("--flagfile={}", path.display()), "--flagfile_1=from_args"]));
+    ::std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(outcome, Ok(ParseOutcome::Parsed(ParsedArgs::default())));
+    assert_eq!(flagfile_1::CONFIG.get_value(), "from_args".to_owned());
+    assert_eq!(flagfile_2::CONFIG.get_value(), "from_file".to_owned());
+    assert_eq!(flagfile_1::CONFIG.source(), ValueSource::CommandLine);
+    assert_eq!(flagfile_2::CONFIG.source(), ValueSource::File(path, 2));
+  }
+
+  #[test]
+  fn flagfiles_override_earlier_flags() {
+    let path = ::std::env::temp_dir().join(format!("zcfg_flagfile_late_{}.flags", ::std::process::id()));
+    ::std::fs::write(&path, "--flagfile_3=from_file\n").unwrap();
+
+    let outcome = FlagParser::new().parse_from_args(
+      args(&["--flagfile_3=from_args", &format!("--flagfile={}", path.display())]));
+    ::std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(outcome, Ok(ParseOutcome::Parsed(ParsedArgs::default())));
+    assert_eq!(flagfile_3::CONFIG.get_value(), "from_file".to_owned());
+    assert_eq!(flagfile_3::CONFIG.source(), ValueSource::File(path, 1));
+  }
+
+  #[test]
+  fn repeated_flags_accumulate_for_maps() {
+    let outcome = FlagParser::new().parse_from_args(
+      args(&["--weights_1=a=1", "--weights_1", "b=2,c=3"]));
+
+    assert_eq!(outcome, Ok(ParseOutcome::Parsed(ParsedArgs::default())));
+    assert_eq!(weights_1::CONFIG.get_value().into_iter().collect::<Vec<(String, u32)>>(),
+               vec![("a".to_owned(), 1), ("b".to_owned(), 2), ("c".to_owned(), 3)]);
+  }
+
+  #[test]
+  fn repeated_flags_accumulate_for_lists() {
+    let outcome = FlagParser::new().parse_from_args(args(&["--tags_1=a", "--tags_1", "b,c", "--tags_1=d"]));
+
+    assert_eq!(outcome, Ok(ParseOutcome::Parsed(ParsedArgs::default())));
+    assert_eq!(tags_1::CONFIG.get_value(), vec!["a", "b", "c", "d"]);
+  }
+
+  #[test]
+  fn optional_configs_behave_like_their_contents() {
+    let outcome = FlagParser::new().parse_from_args(
+      args(&["--optional_tags_1", "a", "--optional_tags_1=b", "--optional_switch_1"]));
+
+    assert_eq!(outcome, Ok(ParseOutcome::Parsed(ParsedArgs::default())));
+    assert_eq!(optional_tags_1::CONFIG.get_value(), Some(vec!["a".to_owned(), "b".to_owned()]));
+    assert_eq!(optional_switch_1::CONFIG.get_value(), Some(true));
+  }
+
+  #[test]
+  fn repeated_scalar_flags_follow_their_policy() {
+    let outcome = FlagParser::new().parse_from_args(args(&["--last_wins_1=1", "--last_wins_1=2"]));
+    assert_eq!(outcome, Ok(ParseOutcome::Parsed(ParsedArgs::default())));
+    assert_eq!(last_wins_1::CONFIG.get_value(), 2);
+
+    let outcome = FlagParser::new().parse_from_args(args(&["--repeated_1=1", "--repeated_1=2"]));
+    assert_eq!(outcome, Err(vec![FlagInitErr::RepeatedFlag("repeated_1".to_owned())]));
+    assert_eq!(outcome.unwrap_err()[0].to_string(), "flag --repeated_1 was given more than once");
+  }
+
+  #[test]
+  fn invalid_values_name_the_flag_and_position() {
+    let outcome = FlagParser::new().parse_from_args(args(&["pos", "--invalid_1", "1,2x"]));
+
+    match outcome {
+      Err(ref errs) => match errs.as_slice() {
+        [FlagInitErr::InitErr { flag, position, err: InitErr::FailedToParse { err, .. } }] => {
+          assert_eq!((flag.as_str(), *position), ("invalid_1", 1));
+          assert_eq!((err.input(), err.span()), ("1,2x", 2..4));
+          assert_eq!(errs[0].to_string(), "argument 1 (--invalid_1): failed to parse value for `invalid_1`: \
+                                           list element 1: invalid digit found in string, expected u32");
+        },
+        _ => panic!("unexpected errors {:?}", errs),
+      },
+      Ok(_) => panic!("unexpected success"),
+    }
+  }
 }
@@ -0,0 +1,81 @@
+use std::cmp;
+use zcfg::ConfigMetadata;
+
+/** The most suggestions offered for a single unknown flag. */
+const MAX_SUGGESTIONS: usize = 3;
+
+/**
+ * Finds the configs whose names are closest to an unknown flag name.
+ *
+ * Candidates must be within an edit distance of a third of the name's length (but at least 2), and
+ * are ordered from closest to furthest.
+ */
+pub fn suggest_configs(name: &str, metadata: &[ConfigMetadata]) -> Vec<ConfigMetadata> {
+  let max_distance = cmp::max(2, name.chars().count() / 3);
+  let mut candidates = metadata.iter()
+    .map(|m| (edit_distance(name, m.config_name()), m))
+    .filter(|&(distance, _)| distance <= max_distance)
+    .collect::<Vec<(usize, &ConfigMetadata)>>();
+  candidates.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.config_name().cmp(b.1.config_name())));
+  candidates.into_iter()
+    .take(MAX_SUGGESTIONS)
+    .map(|(_, m)| m.clone())
+    .collect()
+}
+
+/** The Levenshtein distance between two strings, counted in chars. */
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b_chars = b.chars().collect::<Vec<char>>();
+  let mut previous_row = (0..=b_chars.len()).collect::<Vec<usize>>();
+  for (i, a_char) in a.chars().enumerate() {
+    let mut current_row = vec![i + 1];
+    for (j, b_char) in b_chars.iter().enumerate() {
+      let substitution = previous_row[j] + if a_char == *b_char { 0 } else { 1 };
+      let insertion = current_row[j] + 1;
+      let deletion = previous_row[j + 1] + 1;
+      current_row.push(cmp::min(substitution, cmp::min(insertion, deletion)));
+    }
+    previous_row = current_row;
+  }
+  previous_row[b_chars.len()]
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn metadata(name: &str) -> ConfigMetadata {
+    ConfigMetadata::new(name.to_owned(), "src/a.rs".to_owned(), 1, "", "u32", None, false)
+  }
+
+  #[test]
+  fn edit_distance_counts_edits() {
+    assert_eq!(edit_distance("", ""), 0);
+    assert_eq!(edit_distance("greting", "greeting"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+  }
+
+  #[test]
+  fn suggestions_are_closest_first() {
+    let configs = vec![
+      metadata("greeting_target"),
+      metadata("greetings"),
+      metadata("greeting"),
+      metadata("net_protocol_timeout_ms"),
+    ];
+
+    let names = suggest_configs("greting", &configs).iter()
+      .map(|m| m.config_name().to_owned())
+      .collect::<Vec<String>>();
+
+    assert_eq!(names, vec!["greeting".to_owned(), "greetings".to_owned()]);
+  }
+
+  #[test]
+  fn long_names_tolerate_more_typos() {
+    let configs = vec![metadata("net_protocol_timeout_ms")];
+
+    assert_eq!(suggest_configs("net_protcol_timout_msec", &configs), configs);
+    assert_eq!(suggest_configs("greeting", &configs), Vec::new());
+  }
+}
@@ -0,0 +1,34 @@
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate zcfg;
+extern crate zcfg_flag_parser;
+
+use zcfg_flag_parser::FlagInitErr;
+use zcfg_flag_parser::FlagParser;
+
+// Linking two configs with one name fails every parse, so they get a test binary of their own.
+mod first {
+  define_pub_cfg!(duplicate_1, u32, 0u32, "some duplicate_1 configuration");
+}
+
+mod second {
+  define_pub_cfg!(duplicate_1, String, "".to_owned(), "some other duplicate_1 configuration");
+}
+
+#[test]
+fn duplicate_configs_are_reported_with_every_location() {
+  let errs = FlagParser::new().parse_from_args(vec!["--duplicate_1=5".to_owned()].into_iter()).unwrap_err();
+
+  match errs.as_slice() {
+    [FlagInitErr::DuplicateConfig { name, locations }] => {
+      assert_eq!(name, "duplicate_1");
+      let mut lines = locations.iter().map(|l| (l.file(), l.line())).collect::<Vec<(&str, u32)>>();
+      lines.sort();
+      assert_eq!(lines, vec![(file!(), 12), (file!(), 16)]);
+    },
+    _ => panic!("unexpected errors {:?}", errs),
+  }
+  assert_eq!(first::duplicate_1::CONFIG.get_value(), 0);
+  assert_eq!(second::duplicate_1::CONFIG.get_value(), "".to_owned());
+}
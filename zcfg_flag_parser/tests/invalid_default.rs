@@ -0,0 +1,26 @@
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate zcfg;
+extern crate zcfg_flag_parser;
+
+use zcfg::InitErr;
+use zcfg::ValueSource;
+use zcfg_flag_parser::FlagInitErr;
+use zcfg_flag_parser::FlagParser;
+
+// An invalid default fails every parse, so it gets a test binary of its own.
+define_cfg!(invalid_default_1, String, "".to_owned(), "some invalid_default_1 configuration",
+            validate: [::zcfg::validate::non_empty()]);
+define_cfg!(invalid_default_2, u32, 0u32, "some invalid_default_2 configuration");
+
+#[test]
+fn invalid_defaults_fail_the_parse() {
+  let errs = FlagParser::new().parse_from_args(vec!["--invalid_default_2=2".to_owned()].into_iter()).unwrap_err();
+
+  assert_eq!(errs, vec![FlagInitErr::InvalidDefault(InitErr::ValidationFailed {
+    config_name: "invalid_default_1".to_owned(),
+    err: "value must not be empty".to_owned(),
+  })]);
+  assert_eq!(invalid_default_2::CONFIG.source(), ValueSource::Default);
+}